@@ -6,3 +6,13 @@ fn create_account(username: ft_sdk::Required<"username">) -> ft_sdk::form::Resul
 
     ft_sdk::form::redirect(format!("/foo/?username={username}"))
 }
+
+#[ft_sdk::data]
+fn csrf_token(ft_sdk::CsrfToken(token): ft_sdk::CsrfToken) -> ft_sdk::data::Result {
+    ft_sdk::data::json(token)
+}
+
+#[ft_sdk::form(csrf, middleware = [ft_sdk::middleware::SecurityHeaders])]
+fn change_username(username: ft_sdk::Required<"username">) -> ft_sdk::form::Result {
+    ft_sdk::form::redirect(format!("/foo/?username={username}"))
+}
@@ -0,0 +1,60 @@
+#[derive(serde::Deserialize)]
+struct ItemPath {
+    id: i64,
+}
+
+const MIDDLEWARE: &[&dyn ft_sdk::Middleware] = &[&ft_sdk::middleware::SecurityHeaders];
+
+#[ft_sdk::route("/items/", method = "GET")]
+fn list_items() -> ft_sdk::data::Result {
+    ft_sdk::data::json(vec![1, 2, 3])
+}
+
+#[ft_sdk::route("/items/{id}/", method = "GET")]
+fn show_item(ft_sdk::PathParams(p): ft_sdk::PathParams<ItemPath>) -> ft_sdk::data::Result {
+    ft_sdk::data::json(p.id)
+}
+
+#[ft_sdk::route("/items/{id}/", method = "POST")]
+fn update_item(ft_sdk::PathParams(p): ft_sdk::PathParams<ItemPath>) -> ft_sdk::form::Result {
+    ft_sdk::form::redirect(format!("/items/{}/", p.id))
+}
+
+ft_sdk::router!(api, list_items, show_item, update_item, search);
+
+#[ft_sdk::data(middleware = MIDDLEWARE)]
+fn items() -> ft_sdk::data::Result {
+    ft_sdk::data::json(vec![1, 2, 3])
+}
+
+#[ft_sdk::processor(middleware = [ft_sdk::middleware::SecurityHeaders])]
+fn item_count() -> ft_sdk::processor::Result {
+    ft_sdk::processor::json(3)
+}
+
+#[derive(ft_sdk::FromRequest)]
+struct Search {
+    term: String,
+    #[body(optional)]
+    category: Option<String>,
+    #[query(default)]
+    page: u32,
+    #[header(rename = "user-agent", optional)]
+    user_agent: Option<String>,
+    #[cookie(rename = "fastn-sid", optional)]
+    session: Option<String>,
+    #[param]
+    id: i64,
+}
+
+#[ft_sdk::route("/items/{id}/search/", method = "POST")]
+fn search(s: Search) -> ft_sdk::data::Result {
+    ft_sdk::data::json(serde_json::json!({
+        "term": s.term,
+        "category": s.category,
+        "page": s.page,
+        "user_agent": s.user_agent,
+        "logged_in": s.session.is_some(),
+        "id": s.id,
+    }))
+}
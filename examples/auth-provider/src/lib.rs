@@ -1,5 +1,5 @@
 const PROVIDER_ID: &str = "sample";
-const HASHED_PASSWORD: &str = "hashed-password";
+const HASHED_PASSWORD: &str = ft_sdk::auth::password::PASSWORD_HASH_KEY;
 
 #[ft_sdk::handle_http]
 fn handle(in_: ft_sdk::In, mut conn: ft_sdk::Connection) -> ft_sdk::chr::Result {
@@ -26,7 +26,7 @@ fn create_account(in_: ft_sdk::In, conn: &mut ft_sdk::Connection) -> ft_sdk::chr
         conn,
         PROVIDER_ID,
         email.as_str(),
-        to_provider_data(email.as_str(), password.as_str()),
+        to_provider_data(email.as_str(), password.as_str())?,
     )?;
 
     // TODO: not fond of create_user not logging user in. There is no use case yet for
@@ -57,13 +57,16 @@ fn validate_email(
     }
 }
 
-fn to_provider_data(email: &str, password: &str) -> Vec<ft_sdk::auth::UserData> {
-    vec![
+fn to_provider_data(
+    email: &str,
+    password: &str,
+) -> Result<Vec<ft_sdk::auth::UserData>, ft_sdk::auth::password::PasswordError> {
+    Ok(vec![
         ft_sdk::auth::UserData::Email(email.to_string()),
         ft_sdk::auth::UserData::Identity(email.to_string()),
         ft_sdk::auth::UserData::Custom {
             key: HASHED_PASSWORD.to_string(),
-            value: password.into(),
+            value: ft_sdk::auth::password::hash(password)?.into(),
         },
-    ]
+    ])
 }
@@ -2,37 +2,195 @@ extern crate self as ft_derive;
 
 #[proc_macro_attribute]
 pub fn processor(
-    _attr: proc_macro::TokenStream,
+    attr: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    handle(item, "processor", "handler")
+    handle(attr, item, "processor", "handler")
 }
 
 #[proc_macro_attribute]
 pub fn wrapped_processor(
-    _attr: proc_macro::TokenStream,
+    attr: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    handle(item, "processor", "wrapped_processor")
+    handle(attr, item, "processor", "wrapped_processor")
 }
 
 #[proc_macro_attribute]
 pub fn data(
-    _attr: proc_macro::TokenStream,
+    attr: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    handle(item, "data", "handler")
+    handle(attr, item, "data", "handler")
 }
 
 #[proc_macro_attribute]
 pub fn form(
-    _attr: proc_macro::TokenStream,
+    attr: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    handle(item, "form", "handler")
+    handle(attr, item, "form", "handler")
 }
 
-fn handle(item: proc_macro::TokenStream, kind: &str, handler: &str) -> proc_macro::TokenStream {
+/// Register a handler with [`ft_sdk::Router`], e.g. `#[ft_sdk::route("/items/{id}/", method =
+/// "POST")]`. If `method` is not passed, the route matches all methods.
+///
+/// This keeps the function as it is, and generates a `<name>__route()` function returning the
+/// `ft_sdk::Route`, which is used by `ft_sdk::router!`.
+#[proc_macro_attribute]
+pub fn route(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let RouteArgs { pattern, method } = syn::parse_macro_input!(attr as RouteArgs);
+    let item_fn = syn::parse_macro_input!(item as syn::ItemFn);
+
+    let vis = &item_fn.vis;
+    let fn_name = &item_fn.sig.ident;
+    let fn_name_route = syn::Ident::new(format!("{}__route", fn_name).as_str(), fn_name.span());
+    let method = match method {
+        Some(m) => quote::quote! { Some(#m) },
+        None => quote::quote! { None },
+    };
+
+    let expanded = quote::quote! {
+        #item_fn
+
+        #[doc(hidden)]
+        #[allow(non_snake_case)]
+        #vis fn #fn_name_route() -> ft_sdk::Route {
+            ft_sdk::Route::new(#method, #pattern, #fn_name)
+        }
+    };
+
+    proc_macro::TokenStream::from(expanded)
+}
+
+struct RouteArgs {
+    pattern: syn::LitStr,
+    method: Option<syn::LitStr>,
+}
+
+impl syn::parse::Parse for RouteArgs {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let pattern = input.parse()?;
+        let mut method = None;
+
+        while !input.is_empty() {
+            input.parse::<syn::Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let key: syn::Ident = input.parse()?;
+            input.parse::<syn::Token![=]>()?;
+            match key.to_string().as_str() {
+                "method" => method = Some(input.parse()?),
+                _ => return Err(syn::Error::new(key.span(), "unknown route argument")),
+            }
+        }
+
+        Ok(RouteArgs { pattern, method })
+    }
+}
+
+/// Export a single entrypoint that dispatches to the given `#[ft_sdk::route]` handlers.
+///
+/// `ft_sdk::router!(api, list_items, update_item)` exports `api__entrypoint`.
+#[proc_macro]
+pub fn router(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let parser = syn::punctuated::Punctuated::<syn::Path, syn::Token![,]>::parse_terminated;
+    let mut paths = syn::parse_macro_input!(input with parser).into_iter();
+
+    let name = match paths.next().and_then(|p| p.get_ident().cloned()) {
+        Some(v) => v,
+        None => return compiler_error("expected entrypoint name, e.g. `ft_sdk::router!(api, ..)`"),
+    };
+    let fn_name_entrypoint = syn::Ident::new(format!("{}__entrypoint", name).as_str(), name.span());
+
+    let routes = paths.map(|mut p| {
+        let last = p.segments.last_mut().unwrap();
+        last.ident = syn::Ident::new(format!("{}__route", last.ident).as_str(), last.ident.span());
+        quote::quote! { .with_route(#p()) }
+    });
+
+    let expanded = quote::quote! {
+        #[unsafe(no_mangle)]
+        pub extern "C" fn #fn_name_entrypoint() {
+            ft_sdk::Router::new()
+                #(#routes)*
+                .handle()
+        }
+    };
+
+    proc_macro::TokenStream::from(expanded)
+}
+
+/// Arguments accepted by `#[ft_sdk::data]`, `#[ft_sdk::form]` etc.
+#[derive(Default)]
+struct HandlerArgs {
+    /// `middleware = [A, B]` or `middleware = SOME_CONST`.
+    middleware: Option<syn::Expr>,
+    /// `csrf`, check the CSRF token using `ft_sdk::csrf::Csrf` before other middleware.
+    csrf: bool,
+}
+
+impl syn::parse::Parse for HandlerArgs {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut args = HandlerArgs::default();
+
+        while !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            match key.to_string().as_str() {
+                "middleware" => {
+                    input.parse::<syn::Token![=]>()?;
+                    args.middleware = Some(input.parse()?);
+                }
+                "csrf" => args.csrf = true,
+                _ => return Err(syn::Error::new(key.span(), "unknown argument")),
+            }
+            if !input.is_empty() {
+                input.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+impl HandlerArgs {
+    /// The middleware as a `&[&dyn ft_sdk::Middleware]` expression.
+    fn middleware(&self) -> syn::Result<proc_macro2::TokenStream> {
+        let csrf = if self.csrf {
+            Some(quote::quote! { &ft_sdk::csrf::Csrf as &dyn ft_sdk::Middleware, })
+        } else {
+            None
+        };
+
+        Ok(match self.middleware {
+            None => quote::quote! { &[#csrf] },
+            Some(syn::Expr::Array(ref a)) => {
+                let elems = a.elems.iter();
+                quote::quote! { &[#csrf #(&#elems as &dyn ft_sdk::Middleware),*] }
+            }
+            Some(ref e) if self.csrf => {
+                return Err(syn::Error::new_spanned(
+                    e,
+                    "`csrf` can not be combined with a middleware const, add \
+                    `&ft_sdk::csrf::Csrf` at the start of the const instead",
+                ));
+            }
+            Some(ref e) => quote::quote! { #e },
+        })
+    }
+}
+
+fn handle(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+    kind: &str,
+    handler: &str,
+) -> proc_macro::TokenStream {
+    let args = syn::parse_macro_input!(attr as HandlerArgs);
     let syn::ItemFn {
         attrs,
         vis,
@@ -46,7 +204,12 @@ fn handle(item: proc_macro::TokenStream, kind: &str, handler: &str) -> proc_macr
     let return_type: syn::Type =
         syn::parse_str(format!("ft_sdk::{kind}::Result").as_str()).unwrap();
     let handler: syn::Path =
-        syn::parse_str(format!("ft_sdk::from_request::{handler}::handle").as_str()).unwrap();
+        syn::parse_str(format!("ft_sdk::from_request::{handler}::handle_with_middleware").as_str())
+            .unwrap();
+    let middleware = match args.middleware() {
+        Ok(v) => v,
+        Err(e) => return e.to_compile_error().into(),
+    };
 
     match sig.output {
         syn::ReturnType::Default => {
@@ -70,7 +233,7 @@ fn handle(item: proc_macro::TokenStream, kind: &str, handler: &str) -> proc_macr
     let expanded = quote::quote! {
         #[unsafe(no_mangle)]
         pub extern "C" fn #fn_name_entrypoint() {
-            #handler(#fn_name)
+            #handler(#middleware, #fn_name)
         }
 
         #(#attrs)*
@@ -88,3 +251,179 @@ fn compiler_error(msg: &str) -> proc_macro::TokenStream {
         compile_error!(#msg);
     })
 }
+
+/// Build a struct from the request, reading each field from the source picked by its attribute:
+///
+/// - `#[body]`: from the JSON body, this is the default if there is no attribute.
+/// - `#[query]`: from the query string, or from the body if not in the query string.
+/// - `#[cookie]`: from the cookie of the same name.
+/// - `#[header]`: from the header of the same name, with `_` replaced by `-`.
+/// - `#[param]`: from the `{param}` segment of the route matched by `ft_sdk::Router`. This is not
+///   called `#[path]` as that name is taken by the builtin `#[path = "file"]` attribute.
+///
+/// Each attribute accepts `rename = "name"` to read a different key, `optional` for `Option<T>`
+/// fields that are `None` when the value is missing, and `default` to use `T::default()` when
+/// the value is missing. All fields are read, and errors about all of them are returned together.
+///
+/// ```rust,ignore
+/// #[derive(ft_sdk::FromRequest)]
+/// struct Signup {
+///     name: String,
+///     #[body(optional)]
+///     bio: Option<String>,
+///     #[query(default)]
+///     page: u32,
+///     #[header(rename = "user-agent", optional)]
+///     ua: Option<String>,
+/// }
+/// ```
+#[proc_macro_derive(FromRequest, attributes(body, query, cookie, header, param))]
+pub fn derive_from_request(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(item as syn::DeriveInput);
+
+    let fields = match input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(ref f),
+            ..
+        }) => &f.named,
+        _ => {
+            return compiler_error(
+                "ft_sdk::FromRequest can only be derived for structs with named fields",
+            );
+        }
+    };
+
+    let mut reads = vec![];
+    let mut names = vec![];
+    for field in fields {
+        let name = field.ident.as_ref().unwrap();
+        let f = match FieldSource::from_field(field) {
+            Ok(v) => v,
+            Err(e) => return e.to_compile_error().into(),
+        };
+        let read = f.read();
+        reads.push(quote::quote! {
+            let #name = __ft_errors.check(#read)?;
+        });
+        names.push(name);
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let expanded = quote::quote! {
+        impl #impl_generics ft_sdk::FromRequest for #ident #ty_generics #where_clause {
+            fn from_request(
+                __ft_req: &ft_sdk::__private::http::Request<ft_sdk::__private::serde_json::Value>,
+            ) -> ft_sdk::Result<Self> {
+                // reserved names, so they are not shadowed by the fields read into locals
+                let mut __ft_errors = ft_sdk::from_request::FieldErrors::default();
+                #(#reads)*
+                __ft_errors.finish()?;
+                Ok(#ident {
+                    #(#names: #names.unwrap()),*
+                })
+            }
+        }
+    };
+
+    proc_macro::TokenStream::from(expanded)
+}
+
+enum Source {
+    Body,
+    Query,
+    Cookie,
+    Header,
+    Path,
+}
+
+enum Missing {
+    Error,
+    None,
+    Default,
+}
+
+struct FieldSource {
+    source: Source,
+    key: String,
+    missing: Missing,
+}
+
+impl FieldSource {
+    fn from_field(field: &syn::Field) -> syn::Result<FieldSource> {
+        let name = field.ident.as_ref().unwrap().to_string();
+        let mut f = FieldSource {
+            source: Source::Body,
+            key: name.clone(),
+            missing: Missing::Error,
+        };
+
+        let mut found = false;
+        for attr in field.attrs.iter() {
+            let source = match attr.path().get_ident().map(|i| i.to_string()).as_deref() {
+                Some("body") => Source::Body,
+                Some("query") => Source::Query,
+                Some("cookie") => Source::Cookie,
+                Some("header") => Source::Header,
+                Some("param") => Source::Path,
+                _ => continue,
+            };
+            if found {
+                return Err(syn::Error::new_spanned(attr, "only one source is allowed"));
+            }
+            found = true;
+
+            if let Source::Header = source {
+                f.key = name.replace('_', "-");
+            }
+            f.source = source;
+
+            if let syn::Meta::Path(_) = attr.meta {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    f.key = meta.value()?.parse::<syn::LitStr>()?.value();
+                } else if meta.path.is_ident("optional") {
+                    f.missing = Missing::None;
+                } else if meta.path.is_ident("default") {
+                    f.missing = Missing::Default;
+                } else {
+                    return Err(meta.error("expected `rename`, `optional` or `default`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(f)
+    }
+
+    /// Expression evaluating to `ft_sdk::Result<T>` for the field.
+    fn read(&self) -> proc_macro2::TokenStream {
+        let key = &self.key;
+        let fields = quote::quote! { ft_sdk::from_request::fields };
+
+        if let Source::Body = self.source {
+            return match self.missing {
+                Missing::Error => quote::quote! { #fields::body(#key, __ft_req) },
+                Missing::None => quote::quote! { #fields::body_optional(#key, __ft_req) },
+                Missing::Default => quote::quote! { #fields::body_default(#key, __ft_req) },
+            };
+        }
+
+        let value = match self.source {
+            Source::Body => unreachable!(),
+            Source::Query => quote::quote! { #fields::query_optional(#key, __ft_req) },
+            Source::Cookie => quote::quote! { #fields::cookie(#key, __ft_req) },
+            Source::Header => quote::quote! { #fields::header(#key, __ft_req) },
+            Source::Path => quote::quote! { #fields::path(#key, __ft_req) },
+        };
+
+        match self.missing {
+            Missing::Error => quote::quote! { #fields::required(#key, #value) },
+            Missing::None => quote::quote! { #fields::optional(#key, #value) },
+            Missing::Default => quote::quote! { #fields::default(#key, #value) },
+        }
+    }
+}
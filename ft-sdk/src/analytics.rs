@@ -0,0 +1,174 @@
+//! Visitor tracking and product events, kept in the app's own database.
+//!
+//! The [Tracker] extractor gives each browser a random id, in the [TRACKER_KEY] cookie, and
+//! records when it was first and last seen in the `fastn_tracker` table. When the visitor logs in,
+//! `ft_sdk::auth::provider::login` links the tracker to the user, so events from before the
+//! login count for the user too. [track] records an event, e.g. each step of a signup funnel:
+//!
+//! ```rust,ignore
+//! #[ft_sdk::form]
+//! fn add_to_cart(
+//!     tracker: ft_sdk::Tracker,
+//!     mut conn: ft_sdk::Connection,
+//! ) -> ft_sdk::form::Result {
+//!     let props = serde_json::json!({"sku": 12});
+//!     ft_sdk::analytics::track(&mut conn, &tracker, "add-to-cart", props)?;
+//!     ft_sdk::form::reload()
+//! }
+//! ```
+//!
+//! The tables have to be created by the app, add this migration to `FASTN.ftd`:
+//!
+//! ```ftd
+//! -- fastn.migration: fastn_tracker
+//!
+//! CREATE TABLE IF NOT EXISTS fastn_tracker
+//! (
+//!     id         TEXT PRIMARY KEY,
+//!     uid        INTEGER NULL REFERENCES fastn_user (id) ON DELETE SET NULL,
+//!     first_seen INTEGER NOT NULL,
+//!     last_seen  INTEGER NOT NULL
+//! ) STRICT;
+//!
+//! CREATE TABLE IF NOT EXISTS fastn_event
+//! (
+//!     id         INTEGER PRIMARY KEY,
+//!     tracker_id TEXT NOT NULL REFERENCES fastn_tracker (id) ON DELETE CASCADE,
+//!     name       TEXT NOT NULL,
+//!     props      TEXT NOT NULL,
+//!     created_at INTEGER NOT NULL
+//! ) STRICT;
+//!
+//! CREATE INDEX IF NOT EXISTS fastn_event_name ON fastn_event (name, created_at);
+//! ```
+//!
+//! Apps that do not use [Tracker] never set the cookie, and do not need the tables. Linking the
+//! tracker on login is best effort, and a cookie set by another app on the domain is harmless.
+
+pub use ft_sys_shared::TRACKER_KEY;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AnalyticsError {
+    #[error("db error: {0}")]
+    DatabaseError(#[from] diesel::result::Error),
+    #[error("json error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("user data error: {0}")]
+    UserData(#[from] ft_sdk::auth::UserDataError),
+}
+
+/// The visitor of the current request. A new tracker is created, and its cookie set on the
+/// response, if the request has none, or it is not in the `fastn_tracker` table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tracker(pub String);
+
+thread_local! {
+    static CURRENT: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Set the tracker id of the current request, called when the request is read.
+pub(crate) fn set_current(tracker_id: Option<String>) {
+    CURRENT.with(|c| *c.borrow_mut() = tracker_id.filter(|v| !v.is_empty()));
+}
+
+fn current() -> Option<String> {
+    CURRENT.with(|c| c.borrow().clone())
+}
+
+impl ft_sdk::FromRequest for Tracker {
+    fn from_request(_req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        ft_sdk::from_request::connection::with(|conn| Ok(current_or_create(conn)?))
+    }
+}
+
+fn current_or_create(conn: &mut ft_sdk::Connection) -> Result<Tracker, AnalyticsError> {
+    use diesel::prelude::*;
+    use ft_sdk::schema::fastn_tracker;
+
+    let now = ft_sdk::env::now();
+    if let Some(id) = current() {
+        let seen = diesel::update(fastn_tracker::table.filter(fastn_tracker::id.eq(id.as_str())))
+            .set(fastn_tracker::last_seen.eq(now))
+            .execute(conn)?;
+        if seen == 1 {
+            return Ok(Tracker(id));
+        }
+    }
+
+    let id = ft_sdk::Rng::generate_key(32);
+    let uid = ft_sdk::auth::user_id(conn)?.map(|u| u.0);
+    diesel::insert_into(fastn_tracker::table)
+        .values((
+            fastn_tracker::id.eq(id.as_str()),
+            fastn_tracker::uid.eq(uid),
+            fastn_tracker::first_seen.eq(now),
+            fastn_tracker::last_seen.eq(now),
+        ))
+        .execute(conn)?;
+
+    set_current(Some(id.clone()));
+    ft_sdk::chr::add_pending_cookie(cookie(&id));
+    Ok(Tracker(id))
+}
+
+/// Long-lived, and `SameSite=Lax` so visitors arriving from links on other sites are recognised.
+fn cookie(id: &str) -> ft_sdk::cookie::SetCookie {
+    ft_sdk::cookie::SetCookie::new(TRACKER_KEY, id)
+        .with_path("/")
+        .with_same_site(ft_sdk::cookie::SameSite::Lax)
+}
+
+/// Link the tracker of the current request, if it has one, to `user_id`. Failures are logged and
+/// otherwise ignored, so logging in does not depend on analytics.
+#[cfg(feature = "auth-provider")]
+pub(crate) fn link_current(conn: &mut ft_sdk::Connection, user_id: &ft_sdk::UserId) {
+    use diesel::prelude::*;
+    use ft_sdk::schema::fastn_tracker;
+
+    let id = match current() {
+        Some(v) => v,
+        None => return,
+    };
+    // the cookie may have been set by another app on the same domain, which has the table while
+    // this one does not. In a savepoint, so the error does not abort the caller's transaction.
+    let r = conn.transaction(|conn| {
+        diesel::update(fastn_tracker::table.filter(fastn_tracker::id.eq(id)))
+            .set(fastn_tracker::uid.eq(Some(user_id.0)))
+            .execute(conn)
+    });
+    if let Err(e) = r {
+        ft_sdk::println!("analytics: failed to link tracker to user: {e}");
+    }
+}
+
+/// Record the event `name` for the visitor, with `props` stored as JSON.
+pub fn track<P: serde::Serialize>(
+    conn: &mut ft_sdk::Connection,
+    tracker: &Tracker,
+    name: &str,
+    props: P,
+) -> Result<(), AnalyticsError> {
+    use diesel::prelude::*;
+    use ft_sdk::schema::fastn_event;
+
+    diesel::insert_into(fastn_event::table)
+        .values((
+            fastn_event::tracker_id.eq(tracker.0.as_str()),
+            fastn_event::name.eq(name),
+            fastn_event::props.eq(serde_json::to_string(&props)?),
+            fastn_event::created_at.eq(ft_sdk::env::now()),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn cookie() {
+        assert_eq!(
+            super::cookie("t1").to_string(),
+            "fastn-tid=t1; Secure; HttpOnly; SameSite=Lax; Max-Age=34560000; Path=/"
+        );
+    }
+}
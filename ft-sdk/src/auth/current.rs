@@ -0,0 +1,317 @@
+//! The session and user of the current request, loaded once per request.
+
+struct Loaded {
+    user: Option<User>,
+    session_data: serde_json::Map<String, serde_json::Value>,
+}
+
+struct User {
+    id: ft_sdk::UserId,
+    identity: Option<String>,
+    name: Option<String>,
+    data: std::collections::HashMap<String, ft_sdk::auth::ProviderData>,
+}
+
+thread_local! {
+    static SESSION_ID: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+    static LOADED: std::cell::RefCell<Option<std::rc::Rc<Loaded>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Set the session id of the current request, called when the request is read.
+pub(crate) fn set_session_id(session_id: Option<String>) {
+    SESSION_ID.with(|s| *s.borrow_mut() = session_id.filter(|v| !v.is_empty()));
+    invalidate();
+}
+
+/// Forget the loaded data, so the next call reads it again from the database. Called whenever
+/// the session or user is changed.
+pub(crate) fn invalidate() {
+    LOADED.with(|l| *l.borrow_mut() = None);
+}
+
+/// The session id from the session cookie of the current request.
+pub fn session_id() -> Option<ft_sdk::SessionID> {
+    SESSION_ID.with(|s| s.borrow().clone().map(ft_sdk::SessionID))
+}
+
+fn load(
+    conn: &mut ft_sdk::Connection,
+) -> Result<Option<std::rc::Rc<Loaded>>, ft_sdk::auth::UserDataError> {
+    if let Some(l) = LOADED.with(|l| l.borrow().clone()) {
+        return Ok(Some(l));
+    }
+
+    let mut loaded = match session_id() {
+        Some(sid) => load_session(conn, &sid)?,
+        None => None,
+    };
+    // same override as `ft_sdk::CurrentUser`, so both agree on who is logged in
+    if let Some(u) = ft_sdk::auth::debug_logged_in() {
+        let user = Some(debug_user(conn, u.id)?);
+        loaded = match loaded {
+            Some(l) => Some(Loaded { user, ..l }),
+            None => Some(Loaded {
+                user,
+                session_data: Default::default(),
+            }),
+        };
+    }
+
+    let loaded = match loaded {
+        Some(v) => std::rc::Rc::new(v),
+        None => return Ok(None),
+    };
+    LOADED.with(|l| *l.borrow_mut() = Some(loaded.clone()));
+    Ok(Some(loaded))
+}
+
+fn load_session(
+    conn: &mut ft_sdk::Connection,
+    sid: &ft_sdk::SessionID,
+) -> Result<Option<Loaded>, ft_sdk::auth::UserDataError> {
+    use diesel::prelude::*;
+    use ft_sdk::auth::fastn_user;
+    use ft_sdk::schema::fastn_session;
+
+    type Row = (
+        String,
+        chrono::DateTime<chrono::Utc>,
+        chrono::DateTime<chrono::Utc>,
+        Option<i64>,
+        Option<String>,
+        Option<String>,
+        Option<String>,
+    );
+    let row: Option<Row> = fastn_session::table
+        .left_join(fastn_user::table)
+        .filter(fastn_session::id.eq(sid.0.as_str()))
+        .select((
+            fastn_session::data,
+            fastn_session::created_at,
+            fastn_session::updated_at,
+            fastn_user::id.nullable(),
+            fastn_user::identity.nullable(),
+            fastn_user::name.nullable(),
+            fastn_user::data.nullable(),
+        ))
+        .first(conn)
+        .optional()?;
+
+    let (session_data, created_at, updated_at, id, identity, name, data) = match row {
+        Some(v) => v,
+        // the session cookie points to a session that does not exist any more
+        None => return Ok(None),
+    };
+    if !ft_sdk::session::expiry::check(conn, sid.0.as_str(), created_at, updated_at)? {
+        return Ok(None);
+    }
+
+    let user = match (id, data) {
+        (Some(id), Some(data)) => Some(User {
+            id: ft_sdk::UserId(id),
+            identity,
+            name,
+            data: serde_json::from_str(&data)?,
+        }),
+        _ => None,
+    };
+
+    Ok(Some(Loaded {
+        user,
+        session_data: serde_json::from_str(&session_data)?,
+    }))
+}
+
+/// The user set by `DEBUG_LOGGED_IN`, with their data if they exist in `fastn_user`.
+fn debug_user(conn: &mut ft_sdk::Connection, id: i64) -> Result<User, ft_sdk::auth::UserDataError> {
+    use diesel::prelude::*;
+    use ft_sdk::auth::fastn_user;
+
+    let row: Option<(Option<String>, Option<String>, String)> = fastn_user::table
+        .filter(fastn_user::id.eq(id))
+        .select((fastn_user::identity, fastn_user::name, fastn_user::data))
+        .first(conn)
+        .optional()?;
+
+    Ok(match row {
+        Some((identity, name, data)) => User {
+            id: ft_sdk::UserId(id),
+            identity,
+            name,
+            data: serde_json::from_str(&data)?,
+        },
+        None => User {
+            id: ft_sdk::UserId(id),
+            identity: None,
+            name: None,
+            data: Default::default(),
+        },
+    })
+}
+
+pub(crate) fn user_id(
+    conn: &mut ft_sdk::Connection,
+) -> Result<Option<ft_sdk::UserId>, ft_sdk::auth::UserDataError> {
+    Ok(load(conn)?.and_then(|l| l.user.as_ref().map(|u| u.id.clone())))
+}
+
+/// The logged in user as [ft_sys::UserData], see [to_user_data].
+pub(crate) fn user(
+    conn: &mut ft_sdk::Connection,
+) -> Result<Option<ft_sys::UserData>, ft_sdk::auth::UserDataError> {
+    match load(conn)?.as_ref().and_then(|l| l.user.as_ref()) {
+        Some(u) => to_user_data(u).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// The identity and name come from `fastn_user`, falling back to the first provider that has
+/// one. The email is the first email of the providers, in provider name order.
+fn to_user_data(u: &User) -> Result<ft_sys::UserData, ft_sdk::auth::UserDataError> {
+    let mut providers: Vec<_> = u.data.iter().collect();
+    providers.sort_by_key(|(provider, _)| *provider);
+
+    let identity = u
+        .identity
+        .clone()
+        .or_else(|| {
+            providers
+                .iter()
+                .map(|(_, d)| d.identity.clone())
+                .find(|v| !v.is_empty())
+        })
+        .ok_or(ft_sdk::auth::UserDataError::NoDataFound)?;
+    let name = u
+        .name
+        .clone()
+        .or_else(|| providers.iter().find_map(|(_, d)| d.name.clone()))
+        .unwrap_or_default();
+    let email = providers
+        .iter()
+        .find_map(|(_, d)| d.first_email())
+        .unwrap_or_default();
+    let verified_email = !email.is_empty()
+        && providers
+            .iter()
+            .any(|(_, d)| d.verified_emails.contains(&email));
+
+    Ok(ft_sys::UserData {
+        id: u.id.0,
+        identity,
+        name,
+        email,
+        verified_email,
+    })
+}
+
+pub(crate) fn user_data(
+    conn: &mut ft_sdk::Connection,
+) -> Result<
+    std::collections::HashMap<String, ft_sdk::auth::ProviderData>,
+    ft_sdk::auth::UserDataError,
+> {
+    Ok(load(conn)?
+        .and_then(|l| l.user.as_ref().map(|u| u.data.clone()))
+        .unwrap_or_default())
+}
+
+/// The `provider -> ids` map stored in the session data under
+/// [ft_sdk::auth::SESSION_PROVIDERS_KEY].
+pub(crate) fn session_providers(
+    conn: &mut ft_sdk::Connection,
+) -> Result<std::collections::BTreeMap<String, Vec<String>>, ft_sdk::auth::UserDataError> {
+    Ok(load(conn)?
+        .and_then(|l| {
+            l.session_data
+                .get(ft_sdk::auth::SESSION_PROVIDERS_KEY)
+                .cloned()
+        })
+        .map(serde_json::from_value)
+        .transpose()?
+        .unwrap_or_default())
+}
+
+/// The `provider -> scopes` map stored in the session data under
+/// [ft_sdk::auth::SESSION_SCOPES_KEY].
+pub(crate) fn session_scopes(
+    conn: &mut ft_sdk::Connection,
+) -> Result<std::collections::BTreeMap<String, Vec<ft_sdk::auth::Scope>>, ft_sdk::auth::UserDataError>
+{
+    Ok(load(conn)?
+        .and_then(|l| {
+            l.session_data
+                .get(ft_sdk::auth::SESSION_SCOPES_KEY)
+                .cloned()
+        })
+        .map(serde_json::from_value)
+        .transpose()?
+        .unwrap_or_default())
+}
+
+pub(crate) fn two_factor_pending_user(
+    conn: &mut ft_sdk::Connection,
+) -> Result<Option<ft_sdk::UserId>, ft_sdk::auth::UserDataError> {
+    Ok(load(conn)?
+        .and_then(|l| {
+            l.session_data
+                .get(ft_sdk::auth::TWO_FACTOR_PENDING_KEY)
+                .cloned()
+        })
+        .and_then(|v| serde_json::from_value::<ft_sdk::auth::TwoFactorPending>(v).ok())
+        .filter(|p| !p.is_expired(ft_sdk::env::now().timestamp()))
+        .map(|p| ft_sdk::UserId(p.uid)))
+}
+
+#[cfg(test)]
+mod test {
+    fn provider(
+        identity: &str,
+        name: Option<&str>,
+        email: Option<&str>,
+    ) -> ft_sdk::auth::ProviderData {
+        ft_sdk::auth::ProviderData {
+            identity: identity.to_string(),
+            username: None,
+            name: name.map(|v| v.to_string()),
+            emails: email.map(|v| v.to_string()).into_iter().collect(),
+            verified_emails: vec![],
+            profile_picture: None,
+            custom: serde_json::Value::Null,
+        }
+    }
+
+    #[test]
+    fn to_user_data() {
+        let mut u = super::User {
+            id: ft_sdk::UserId(1),
+            identity: None,
+            name: None,
+            data: [
+                ("github".to_string(), provider("gh", Some("Ann"), None)),
+                ("email".to_string(), provider("", None, Some("a@b.c"))),
+            ]
+            .into(),
+        };
+        let d = super::to_user_data(&u).unwrap();
+        assert_eq!(d.identity, "gh");
+        assert_eq!(d.name, "Ann");
+        assert_eq!(d.email, "a@b.c");
+        assert!(!d.verified_email);
+
+        u.data
+            .get_mut("email")
+            .unwrap()
+            .verified_emails
+            .push("a@b.c".to_string());
+        u.identity = Some("ann".to_string());
+        let d = super::to_user_data(&u).unwrap();
+        assert_eq!(d.identity, "ann");
+        assert!(d.verified_email);
+
+        // no identity anywhere is an error rather than a panic
+        u.identity = None;
+        u.data.clear();
+        assert!(super::to_user_data(&u).is_err());
+    }
+}
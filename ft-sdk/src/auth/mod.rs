@@ -1,10 +1,23 @@
+pub(crate) mod current;
+#[cfg(feature = "auth-provider")]
+pub mod oauth2;
+#[cfg(feature = "auth-provider")]
+pub mod password;
 #[cfg(feature = "auth-provider")]
 pub mod provider;
+pub mod rbac;
 mod schema;
+#[cfg(feature = "auth-provider")]
+pub mod token;
+#[cfg(feature = "auth-provider")]
+pub mod totp;
 mod utils;
 
+pub use current::session_id;
 pub use ft_sys_shared::SESSION_KEY;
-pub use schema::fastn_user;
+pub use schema::{
+    fastn_auth_token, fastn_role, fastn_role_permission, fastn_user, fastn_user_role,
+};
 pub use utils::{user_data_by_query, Counter};
 
 #[derive(Clone, Debug)]
@@ -38,45 +51,178 @@ impl ProviderData {
     }
 }
 
+/// Key in the session data holding the providers used to log in in this session, as a map of
+/// provider id to the ids the provider knows the user by. See [provider_ids] and
+/// [session_provider_ids].
+pub const SESSION_PROVIDERS_KEY: &str = "fastn-auth-providers";
+
+/// Key in the session data holding the scopes granted to this session, as a map of provider id
+/// to the list of scopes. See [session_scopes] and [session_has_scopes].
+pub const SESSION_SCOPES_KEY: &str = "fastn-auth-scopes";
+
+/// In the current session, we have zero or more scopes dropped by different auth
+/// providers that have been used so far. Each auth provider sdk also provides some
+/// APIs that require certain scopes to be present. Before calling those APIs, the
+/// caller can check if the session has enough scopes to call that api. If not, the
+/// caller can request the user to log in again with the required scopes.
+///
+/// Scopes are granted by `ft_sdk::auth::provider::grant_scopes`, and checked with
+/// [session_has_scopes] or the `ft_sdk::RequireScopes` extractor.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct Scope(pub String);
+
+impl From<&str> for Scope {
+    fn from(s: &str) -> Self {
+        Scope(s.to_string())
+    }
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Key in the session data holding the user who logged in, but has not yet entered their second
+/// factor. The session is not logged in until they do. See `ft_sdk::auth::totp`.
+pub const TWO_FACTOR_PENDING_KEY: &str = "fastn-2fa-pending";
+
+/// Seconds a login waits for the second factor, see [TWO_FACTOR_PENDING_KEY]. After that the
+/// first factor has to be checked again.
+pub const TWO_FACTOR_PENDING_TIMEOUT: i64 = 300;
+
+/// The value of [TWO_FACTOR_PENDING_KEY].
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct TwoFactorPending {
+    pub(crate) uid: i64,
+    /// Unix seconds the first factor was checked at.
+    pub(crate) at: i64,
+    /// Codes entered so far.
+    pub(crate) attempts: u32,
+}
+
+impl TwoFactorPending {
+    pub(crate) fn is_expired(&self, now: i64) -> bool {
+        now.saturating_sub(self.at) > TWO_FACTOR_PENDING_TIMEOUT
+    }
+}
+
+/// Keys in the session data that belong to the logged-in user, cleared by
+/// [ft_sdk::SessionID::logout].
+pub(crate) const SESSION_AUTH_KEYS: [&str; 3] = [
+    SESSION_PROVIDERS_KEY,
+    SESSION_SCOPES_KEY,
+    TWO_FACTOR_PENDING_KEY,
+];
+
 /// Get the currently logged-in user's userid. Returns `None` if the user is not logged in.
-pub fn user_id() -> Option<UserId> {
-    todo!()
+///
+/// This, and the other functions in this module that look at the current user, read the session
+/// cookie of the current request. The session and user are loaded from the database the first
+/// time one of them is called, and reused for the rest of the request.
+///
+/// The `DEBUG_LOGGED_IN` environment variable overrides the user, as for `ft_sdk::CurrentUser`.
+pub fn user_id(conn: &mut ft_sdk::Connection) -> Result<Option<UserId>, UserDataError> {
+    current::user_id(conn)
 }
 
-/// get the currently logged in user's username
-pub fn username(_provider: &str) -> Option<String> {
-    todo!()
+/// get the currently logged in user's username, as given by `provider`
+pub fn username(
+    conn: &mut ft_sdk::Connection,
+    provider: &str,
+) -> Result<Option<String>, UserDataError> {
+    Ok(current::user_data(conn)?
+        .remove(provider)
+        .and_then(|d| d.username))
 }
 
-/// Get all user data stored against the user in the database. Only allowed if scope
-/// auth:* is granted. Based on permission, whatever you have access to will be given.
-pub fn get_user_data() -> std::collections::HashMap<String, ProviderData> {
-    todo!()
+/// Get all user data stored against the user in the database, keyed by provider. Empty if the
+/// user is not logged in.
+pub fn get_user_data(
+    conn: &mut ft_sdk::Connection,
+) -> Result<std::collections::HashMap<String, ProviderData>, UserDataError> {
+    current::user_data(conn)
 }
 
-pub fn is_authenticated() -> bool {
-    todo!()
+pub fn is_authenticated(conn: &mut ft_sdk::Connection) -> Result<bool, UserDataError> {
+    Ok(current::user_id(conn)?.is_some())
 }
 
 /// This gives you a list of IDs related to the provider, for the user.
-pub fn provider_ids(_provider: &str) -> Vec<String> {
-    todo!()
+pub fn provider_ids(
+    conn: &mut ft_sdk::Connection,
+    provider: &str,
+) -> Result<Vec<String>, UserDataError> {
+    Ok(current::user_data(conn)?
+        .remove(provider)
+        .map(|d| vec![d.identity])
+        .unwrap_or_default())
 }
 
 /// This gives you a list of IDs related to the provider, for the session.
-pub fn session_provider_ids(_provider: &str) -> Vec<String> {
-    todo!()
+pub fn session_provider_ids(
+    conn: &mut ft_sdk::Connection,
+    provider: &str,
+) -> Result<Vec<String>, UserDataError> {
+    Ok(current::session_providers(conn)?
+        .remove(provider)
+        .unwrap_or_default())
 }
 
 /// This returns a list of providers whose credentials are attached to the current user
 /// account.
-pub fn providers() -> Vec<String> {
-    todo!()
+pub fn providers(conn: &mut ft_sdk::Connection) -> Result<Vec<String>, UserDataError> {
+    let mut providers: Vec<String> = current::user_data(conn)?.into_keys().collect();
+    providers.sort();
+    Ok(providers)
 }
 
 /// This returns a list of providers whose credentials are attached to this session.
-pub fn session_providers() -> Vec<String> {
-    todo!()
+pub fn session_providers(conn: &mut ft_sdk::Connection) -> Result<Vec<String>, UserDataError> {
+    Ok(current::session_providers(conn)?.into_keys().collect())
+}
+
+/// The scopes `provider` has granted to the current session, see [SESSION_SCOPES_KEY].
+pub fn session_scopes(
+    conn: &mut ft_sdk::Connection,
+    provider: &str,
+) -> Result<Vec<Scope>, UserDataError> {
+    Ok(current::session_scopes(conn)?
+        .remove(provider)
+        .unwrap_or_default())
+}
+
+/// Check if `provider` has granted all of `scopes` to the session `session_id`.
+pub fn session_has_scopes(
+    conn: &mut ft_sdk::Connection,
+    session_id: &ft_sdk::session::SessionID,
+    provider: &str,
+    scopes: &[Scope],
+) -> Result<bool, diesel::result::Error> {
+    let granted: std::collections::BTreeMap<String, Vec<Scope>> = session_id
+        .data(conn)?
+        .get_key(SESSION_SCOPES_KEY)
+        .unwrap_or_default();
+    let granted = granted.get(provider).map(Vec::as_slice).unwrap_or_default();
+    Ok(missing_scopes(granted, scopes).is_empty())
+}
+
+/// The scopes in `required` that are not in `granted`.
+pub(crate) fn missing_scopes(granted: &[Scope], required: &[Scope]) -> Vec<Scope> {
+    required
+        .iter()
+        .filter(|s| !granted.contains(s))
+        .cloned()
+        .collect()
+}
+
+/// The user waiting to enter their second factor in the current session, see
+/// [TWO_FACTOR_PENDING_KEY].
+pub fn two_factor_pending_user(
+    conn: &mut ft_sdk::Connection,
+) -> Result<Option<UserId>, UserDataError> {
+    current::two_factor_pending_user(conn)
 }
 
 #[cfg(feature = "field-extractors")]
@@ -84,53 +230,42 @@ pub fn ud(
     cookie: ft_sdk::Cookie<SESSION_KEY>,
     conn: &mut ft_sdk::Connection,
 ) -> Result<Option<ft_sys::UserData>, UserDataError> {
-    if let Some(v) = ft_sys::env::var("DEBUG_LOGGED_IN".to_string()) {
-        let mut v = v.splitn(4, ' ');
-        return Ok(Some(ft_sys::UserData {
-            id: v.next().unwrap().parse().unwrap(),
-            identity: v.next().unwrap_or_default().to_string(),
-            name: v.next().map(|v| v.to_string()).unwrap_or_default(),
-            email: v.next().map(|v| v.to_string()).unwrap_or_default(),
-            verified_email: true,
-        }));
-    }
-
     ft_sdk::println!("sid: {cookie}");
 
-    let sid = match cookie.0 {
-        Some(v) => v,
-        None => return Ok(None),
-    };
+    user_data_by_session(conn)
+}
 
-    let (UserId(id), identity, data) = match utils::user_data_by_query(
-        conn,
-        r#"
-            SELECT
-                fastn_user.id as id, identity, fastn_user.data -> 'email' as data
-            FROM fastn_user
-            JOIN fastn_session
-            WHERE
-                fastn_session.id = $1
-                AND fastn_user.id = fastn_session.uid
-            "#,
-        sid.as_str(),
-    ) {
-        Ok(v) => v,
-        Err(UserDataError::NoDataFound) => return Ok(None),
-        Err(e) => return Err(e),
-    };
+/// The user logged in to the current session, if any. The `DEBUG_LOGGED_IN` environment
+/// variable, `<id> <identity> <name> <email>`, overrides the session for local development.
+pub(crate) fn user_data_by_session(
+    conn: &mut ft_sdk::Connection,
+) -> Result<Option<ft_sys::UserData>, UserDataError> {
+    if let Some(v) = debug_logged_in() {
+        return Ok(Some(v));
+    }
 
-    let email = data
-        .first_email()
-        .expect("email provider must have an email");
+    current::user(conn)
+}
 
-    Ok(Some(ft_sys::UserData {
+/// The user set by the `DEBUG_LOGGED_IN` environment variable, `None` if it is not set or does
+/// not start with a valid user id.
+pub(crate) fn debug_logged_in() -> Option<ft_sys::UserData> {
+    let v = ft_sys::env::var("DEBUG_LOGGED_IN".to_string())?;
+    let mut v = v.splitn(4, ' ');
+    let id = match v.next().and_then(|id| id.parse().ok()) {
+        Some(id) => id,
+        None => {
+            ft_sdk::println!("DEBUG_LOGGED_IN must start with the user id, ignoring it");
+            return None;
+        }
+    };
+    Some(ft_sys::UserData {
         id,
-        identity: identity.expect("user fetched from session cookie must have identity"),
-        name: data.name.unwrap_or_default(),
-        email,
-        verified_email: !data.verified_emails.is_empty(),
-    }))
+        identity: v.next().unwrap_or_default().to_string(),
+        name: v.next().map(|v| v.to_string()).unwrap_or_default(),
+        email: v.next().map(|v| v.to_string()).unwrap_or_default(),
+        verified_email: true,
+    })
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -0,0 +1,751 @@
+//! OAuth2 authorization code flow, with PKCE, and OpenID Connect, for social login providers.
+//!
+//! ```rust,ignore
+//! fn client() -> ft_sdk::auth::oauth2::Client {
+//!     ft_sdk::auth::oauth2::Client::new(
+//!         "<client id>",
+//!         "https://accounts.google.com/o/oauth2/v2/auth",
+//!         "https://oauth2.googleapis.com/token",
+//!         "https://example.com/-/auth/google/callback/",
+//!     )
+//!     .with_client_secret("<client secret>")
+//!     .with_scopes(&["openid", "email", "profile"])
+//!     .with_issuer("https://accounts.google.com")
+//!     .with_userinfo_url("https://openidconnect.googleapis.com/v1/userinfo")
+//! }
+//!
+//! // send the user to the provider
+//! let url = client().authorize_url(&mut conn, &session_id)?;
+//!
+//! // in the handler for the redirect uri, with `callback: ft_sdk::auth::oauth2::Callback`
+//! let authorization = client().callback(&mut conn, &session_id, &callback)?;
+//! let data = client().provider_data(&authorization)?;
+//! ```
+//!
+//! The `state`, PKCE verifier and OIDC `nonce` are kept in the session data, under
+//! [OAUTH2_KEY], between [Client::authorize_url] and [Client::callback], and are removed when
+//! the callback is handled.
+//!
+//! The ID token signature is not checked, the token is read from the response of the token
+//! endpoint, over TLS, which OpenID Connect allows in place of checking the signature. Its
+//! claims are checked by [Client::callback], and [Client::provider_data] checks the userinfo is
+//! of the same user.
+
+/// Key in the session data the pending authorization is stored under.
+pub const OAUTH2_KEY: &str = "fastn-oauth2";
+
+#[derive(Debug, thiserror::Error)]
+pub enum OAuth2Error {
+    #[error("no pending authorization in the session")]
+    NoPendingAuthorization,
+    #[error("state does not match the pending authorization")]
+    StateMismatch,
+    #[error("callback has no code")]
+    MissingCode,
+    /// The provider sent an error, to the callback or from the token endpoint.
+    #[error("provider error {error}: {description:?}")]
+    Provider {
+        error: String,
+        description: Option<String>,
+    },
+    #[error("unexpected response {status}: {body}")]
+    UnexpectedResponse { status: u16, body: String },
+    #[error("invalid id token: {0}")]
+    InvalidIdToken(&'static str),
+    #[error("userinfo url is not set")]
+    NoUserinfoUrl,
+    /// The userinfo is of another user than the ID token, OpenID Connect Core 5.3.2.
+    #[error("userinfo sub does not match the id token")]
+    SubjectMismatch,
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("http error: {0}")]
+    Http(#[from] http::Error),
+    #[error("db error: {0}")]
+    Database(#[from] diesel::result::Error),
+    #[error("session error: {0}")]
+    Session(#[from] ft_sdk::session::SetKeyError),
+}
+
+#[derive(Clone, Debug)]
+pub struct Client {
+    client_id: String,
+    client_secret: Option<String>,
+    auth_url: String,
+    token_url: String,
+    redirect_uri: String,
+    scopes: Vec<String>,
+    userinfo_url: Option<String>,
+    issuer: Option<String>,
+}
+
+/// What the token endpoint returns.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    #[serde(default)]
+    pub token_type: Option<String>,
+    /// Lifetime of the access token in seconds.
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// Only sent for the `openid` scope.
+    #[serde(default)]
+    pub id_token: Option<String>,
+}
+
+/// Claims of an OpenID Connect ID token, the ones not listed here are in `other`.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    #[serde(deserialize_with = "one_or_many")]
+    pub aud: Vec<String>,
+    pub exp: i64,
+    #[serde(default)]
+    pub iat: Option<i64>,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub azp: Option<String>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// What [Client::callback] returns: the tokens, and the claims of the ID token, checked, if the
+/// provider sent one. Logins should be bound to `claims.sub` when there are claims.
+#[derive(Clone, Debug)]
+pub struct Authorization {
+    pub tokens: TokenResponse,
+    pub claims: Option<IdTokenClaims>,
+}
+
+/// The query parameters the provider redirects back with.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+pub struct Callback {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+impl ft_sdk::FromRequest for Callback {
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        serde_urlencoded::from_str(req.uri().query().unwrap_or_default())
+            .map_err(|e| ft_sdk::bad_request!("invalid oauth2 callback: {e}").into())
+    }
+}
+
+/// Stored in the session between the redirect to the provider and the callback.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+struct Pending {
+    state: String,
+    verifier: String,
+    nonce: Option<String>,
+}
+
+impl Client {
+    /// `redirect_uri` is where the provider sends the user back to, it usually has to be
+    /// registered with the provider.
+    pub fn new<S: AsRef<str>>(client_id: S, auth_url: S, token_url: S, redirect_uri: S) -> Client {
+        Client {
+            client_id: client_id.as_ref().to_string(),
+            client_secret: None,
+            auth_url: auth_url.as_ref().to_string(),
+            token_url: token_url.as_ref().to_string(),
+            redirect_uri: redirect_uri.as_ref().to_string(),
+            scopes: vec![],
+            userinfo_url: None,
+            issuer: None,
+        }
+    }
+
+    /// Sent in the body of token requests. Not needed by providers that only use PKCE.
+    pub fn with_client_secret<S: AsRef<str>>(mut self, secret: S) -> Client {
+        self.client_secret = Some(secret.as_ref().to_string());
+        self
+    }
+
+    /// Include `openid` to use OpenID Connect.
+    pub fn with_scopes(mut self, scopes: &[&str]) -> Client {
+        self.scopes = scopes.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    pub fn with_userinfo_url<S: AsRef<str>>(mut self, url: S) -> Client {
+        self.userinfo_url = Some(url.as_ref().to_string());
+        self
+    }
+
+    /// The `iss` the ID token must have. Without this the issuer is not checked.
+    pub fn with_issuer<S: AsRef<str>>(mut self, issuer: S) -> Client {
+        self.issuer = Some(issuer.as_ref().to_string());
+        self
+    }
+
+    fn is_oidc(&self) -> bool {
+        self.scopes.iter().any(|s| s == "openid")
+    }
+
+    /// The url to send the user to, to log in with the provider. A new `state` and PKCE
+    /// verifier are stored in `session`, replacing any earlier pending authorization.
+    pub fn authorize_url(
+        &self,
+        conn: &mut ft_sdk::Connection,
+        session: &ft_sdk::SessionID,
+    ) -> Result<String, OAuth2Error> {
+        let pending = Pending {
+            state: ft_sdk::Rng::generate_key(32),
+            // the spec allows 43 to 128 characters
+            verifier: ft_sdk::Rng::generate_key(64),
+            nonce: self.is_oidc().then(|| ft_sdk::Rng::generate_key(32)),
+        };
+        session.set_key(conn, OAUTH2_KEY, &pending)?;
+        Ok(self.authorize_url_for(&pending))
+    }
+
+    fn authorize_url_for(&self, pending: &Pending) -> String {
+        let scope = self.scopes.join(" ");
+        let challenge = code_challenge(&pending.verifier);
+        let mut params = vec![
+            ("response_type", "code"),
+            ("client_id", self.client_id.as_str()),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("state", pending.state.as_str()),
+            ("code_challenge", challenge.as_str()),
+            ("code_challenge_method", "S256"),
+        ];
+        if !scope.is_empty() {
+            params.push(("scope", scope.as_str()));
+        }
+        if let Some(ref nonce) = pending.nonce {
+            params.push(("nonce", nonce.as_str()));
+        }
+
+        let sep = if self.auth_url.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        // serializing string pairs can not fail
+        let query = serde_urlencoded::to_string(params).unwrap_or_default();
+        format!("{}{sep}{query}", self.auth_url)
+    }
+
+    /// Handle the redirect back from the provider: check `callback` against the authorization
+    /// pending in `session`, exchange the code for tokens, and check the ID token if there is
+    /// one. The pending authorization is removed, so a callback can only be used once.
+    pub fn callback(
+        &self,
+        conn: &mut ft_sdk::Connection,
+        session: &ft_sdk::SessionID,
+        callback: &Callback,
+    ) -> Result<Authorization, OAuth2Error> {
+        // taken out of the session, so the state can only be used once
+        let mut pending = None;
+        session.update_key(conn, OAUTH2_KEY, |p: Option<Pending>| {
+            pending = p;
+            None
+        })?;
+        let pending = pending.ok_or(OAuth2Error::NoPendingAuthorization)?;
+
+        let code = check_callback(&pending, callback)?;
+        let tokens = self.exchange_code(code, &pending.verifier)?;
+        let claims = tokens
+            .id_token
+            .as_deref()
+            .map(|t| self.validate_id_token(t, pending.nonce.as_deref()))
+            .transpose()?;
+        Ok(Authorization { tokens, claims })
+    }
+
+    /// Exchange an authorization code for tokens. [Client::callback] does this, along with the
+    /// checks that have to happen first.
+    pub fn exchange_code(&self, code: &str, verifier: &str) -> Result<TokenResponse, OAuth2Error> {
+        self.exchange_code_with(code, verifier, send)
+    }
+
+    fn exchange_code_with(
+        &self,
+        code: &str,
+        verifier: &str,
+        send: impl Fn(http::Request<bytes::Bytes>) -> http::Response<bytes::Bytes>,
+    ) -> Result<TokenResponse, OAuth2Error> {
+        self.token_request(
+            &[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.redirect_uri.as_str()),
+                ("code_verifier", verifier),
+            ],
+            send,
+        )
+    }
+
+    /// Get a new access token using a refresh token. The response may have a new refresh token
+    /// too, which should replace the old one.
+    pub fn refresh(&self, refresh_token: &str) -> Result<TokenResponse, OAuth2Error> {
+        self.refresh_with(refresh_token, send)
+    }
+
+    fn refresh_with(
+        &self,
+        refresh_token: &str,
+        send: impl Fn(http::Request<bytes::Bytes>) -> http::Response<bytes::Bytes>,
+    ) -> Result<TokenResponse, OAuth2Error> {
+        self.token_request(
+            &[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+            ],
+            send,
+        )
+    }
+
+    fn token_request(
+        &self,
+        params: &[(&str, &str)],
+        send: impl Fn(http::Request<bytes::Bytes>) -> http::Response<bytes::Bytes>,
+    ) -> Result<TokenResponse, OAuth2Error> {
+        let mut params = params.to_vec();
+        params.push(("client_id", self.client_id.as_str()));
+        if let Some(ref secret) = self.client_secret {
+            params.push(("client_secret", secret.as_str()));
+        }
+        // serializing string pairs can not fail
+        let body = serde_urlencoded::to_string(params).unwrap_or_default();
+
+        let req = http::Request::builder()
+            .method(http::Method::POST)
+            .uri(self.token_url.as_str())
+            .header(
+                http::header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded",
+            )
+            // GitHub sends a urlencoded response without this
+            .header(http::header::ACCEPT, "application/json")
+            .body(bytes::Bytes::from(body))?;
+
+        parse_response(send(req))
+    }
+
+    /// Fetch the userinfo of the user the access token belongs to.
+    pub fn userinfo(&self, access_token: &str) -> Result<serde_json::Value, OAuth2Error> {
+        self.userinfo_with(access_token, send)
+    }
+
+    fn userinfo_with(
+        &self,
+        access_token: &str,
+        send: impl Fn(http::Request<bytes::Bytes>) -> http::Response<bytes::Bytes>,
+    ) -> Result<serde_json::Value, OAuth2Error> {
+        let url = self
+            .userinfo_url
+            .as_deref()
+            .ok_or(OAuth2Error::NoUserinfoUrl)?;
+        let req = http::Request::builder()
+            .uri(url)
+            .header(
+                http::header::AUTHORIZATION,
+                format!("Bearer {access_token}"),
+            )
+            .header(http::header::ACCEPT, "application/json")
+            // GitHub rejects requests without a user agent
+            .header(http::header::USER_AGENT, "ft-sdk")
+            .body(bytes::Bytes::new())?;
+
+        parse_response(send(req))
+    }
+
+    /// Fetch the userinfo, see [provider_data] for how it is mapped. If there is an ID token,
+    /// the userinfo has to be of the same user, [OAuth2Error::SubjectMismatch] otherwise.
+    pub fn provider_data(
+        &self,
+        authorization: &Authorization,
+    ) -> Result<ft_sdk::auth::ProviderData, OAuth2Error> {
+        self.provider_data_with(authorization, send)
+    }
+
+    fn provider_data_with(
+        &self,
+        authorization: &Authorization,
+        send: impl Fn(http::Request<bytes::Bytes>) -> http::Response<bytes::Bytes>,
+    ) -> Result<ft_sdk::auth::ProviderData, OAuth2Error> {
+        let info = self.userinfo_with(&authorization.tokens.access_token, send)?;
+        if let Some(ref claims) = authorization.claims {
+            if info.get("sub").and_then(|v| v.as_str()) != Some(claims.sub.as_str()) {
+                return Err(OAuth2Error::SubjectMismatch);
+            }
+        }
+        Ok(provider_data(&info))
+    }
+
+    /// Check the claims of an ID token: issuer, if [Client::with_issuer] is set, audience,
+    /// expiry and `nonce`.
+    pub fn validate_id_token(
+        &self,
+        id_token: &str,
+        nonce: Option<&str>,
+    ) -> Result<IdTokenClaims, OAuth2Error> {
+        self.validate_id_token_at(id_token, nonce, ft_sdk::env::now().timestamp())
+    }
+
+    fn validate_id_token_at(
+        &self,
+        id_token: &str,
+        nonce: Option<&str>,
+        now: i64,
+    ) -> Result<IdTokenClaims, OAuth2Error> {
+        let claims = decode_id_token(id_token)?;
+
+        if self.issuer.as_ref().is_some_and(|i| *i != claims.iss) {
+            return Err(OAuth2Error::InvalidIdToken("issuer does not match"));
+        }
+        if !claims.aud.iter().any(|a| *a == self.client_id) {
+            return Err(OAuth2Error::InvalidIdToken("audience does not match"));
+        }
+        if claims.aud.len() > 1 && claims.azp.as_deref() != Some(self.client_id.as_str()) {
+            return Err(OAuth2Error::InvalidIdToken(
+                "authorized party does not match",
+            ));
+        }
+        // a minute of leeway for clock differences
+        if claims.exp + 60 < now {
+            return Err(OAuth2Error::InvalidIdToken("expired"));
+        }
+        if let Some(nonce) = nonce {
+            let matches = claims
+                .nonce
+                .as_deref()
+                .is_some_and(|n| ft_sdk::utils::constant_time_eq(n, nonce));
+            if !matches {
+                return Err(OAuth2Error::InvalidIdToken("nonce does not match"));
+            }
+        }
+
+        Ok(claims)
+    }
+}
+
+/// Map userinfo, or ID token claims, to [ft_sdk::auth::ProviderData]. The standard OpenID
+/// Connect claims are used, falling back to the GitHub names: `sub` or `id` is the identity,
+/// `preferred_username` or `login` the username, and `picture` or `avatar_url` the profile
+/// picture. The email goes to `verified_emails` only if `email_verified` is `true`. All of it is
+/// kept in `custom`.
+pub fn provider_data(claims: &serde_json::Value) -> ft_sdk::auth::ProviderData {
+    let get = |k: &str| match claims.get(k) {
+        Some(serde_json::Value::String(s)) if !s.is_empty() => Some(s.to_string()),
+        Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    };
+
+    let verified = claims
+        .get("email_verified")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let (emails, verified_emails) = match get("email") {
+        Some(e) if verified => (vec![], vec![e]),
+        Some(e) => (vec![e], vec![]),
+        None => (vec![], vec![]),
+    };
+
+    ft_sdk::auth::ProviderData {
+        identity: get("sub").or_else(|| get("id")).unwrap_or_default(),
+        username: get("preferred_username").or_else(|| get("login")),
+        name: get("name"),
+        emails,
+        verified_emails,
+        profile_picture: get("picture").or_else(|| get("avatar_url")),
+        custom: claims.clone(),
+    }
+}
+
+fn send(req: http::Request<bytes::Bytes>) -> http::Response<bytes::Bytes> {
+    ft_sdk::http::send(req).unwrap_or_else(|e| match e {})
+}
+
+/// The code from the callback, if it is for the pending authorization.
+fn check_callback<'a>(pending: &Pending, callback: &'a Callback) -> Result<&'a str, OAuth2Error> {
+    let state_matches = callback
+        .state
+        .as_deref()
+        .is_some_and(|s| ft_sdk::utils::constant_time_eq(s, &pending.state));
+    if !state_matches {
+        return Err(OAuth2Error::StateMismatch);
+    }
+    if let Some(ref error) = callback.error {
+        return Err(OAuth2Error::Provider {
+            error: error.to_string(),
+            description: callback.error_description.clone(),
+        });
+    }
+    callback.code.as_deref().ok_or(OAuth2Error::MissingCode)
+}
+
+fn parse_response<T: serde::de::DeserializeOwned>(
+    res: http::Response<bytes::Bytes>,
+) -> Result<T, OAuth2Error> {
+    #[derive(serde::Deserialize)]
+    struct ErrorResponse {
+        error: String,
+        error_description: Option<String>,
+    }
+
+    if res.status().is_success() {
+        // GitHub sends errors with 200
+        if let Ok(e) = serde_json::from_slice::<ErrorResponse>(res.body()) {
+            return Err(OAuth2Error::Provider {
+                error: e.error,
+                description: e.error_description,
+            });
+        }
+        return Ok(serde_json::from_slice(res.body())?);
+    }
+
+    match serde_json::from_slice::<ErrorResponse>(res.body()) {
+        Ok(e) => Err(OAuth2Error::Provider {
+            error: e.error,
+            description: e.error_description,
+        }),
+        Err(_) => Err(OAuth2Error::UnexpectedResponse {
+            status: res.status().as_u16(),
+            body: String::from_utf8_lossy(res.body()).to_string(),
+        }),
+    }
+}
+
+/// `BASE64URL(SHA256(verifier))`, the `S256` PKCE challenge.
+fn code_challenge(verifier: &str) -> String {
+    use base64ct::Encoding;
+    use sha2::Digest;
+
+    base64ct::Base64UrlUnpadded::encode_string(&sha2::Sha256::digest(verifier.as_bytes()))
+}
+
+fn decode_id_token(id_token: &str) -> Result<IdTokenClaims, OAuth2Error> {
+    use base64ct::Encoding;
+
+    let payload = id_token
+        .split('.')
+        .nth(1)
+        .ok_or(OAuth2Error::InvalidIdToken("not a jwt"))?;
+    let payload = base64ct::Base64UrlUnpadded::decode_vec(payload.trim_end_matches('='))
+        .map_err(|_| OAuth2Error::InvalidIdToken("payload is not base64url"))?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+fn one_or_many<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match serde::Deserialize::deserialize(d)? {
+        OneOrMany::One(v) => vec![v],
+        OneOrMany::Many(v) => v,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    fn client() -> super::Client {
+        super::Client::new(
+            "cid",
+            "https://id.example.com/auth?prompt=login",
+            "https://id.example.com/token",
+            "https://app.example.com/cb/",
+        )
+        .with_client_secret("secret")
+        .with_scopes(&["openid", "email"])
+        .with_issuer("https://id.example.com")
+        .with_userinfo_url("https://id.example.com/userinfo")
+    }
+
+    fn pending() -> super::Pending {
+        super::Pending {
+            state: "st".to_string(),
+            verifier: "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string(),
+            nonce: Some("n1".to_string()),
+        }
+    }
+
+    fn id_token(claims: serde_json::Value) -> String {
+        use base64ct::Encoding;
+
+        let payload = base64ct::Base64UrlUnpadded::encode_string(claims.to_string().as_bytes());
+        format!("eyJhbGciOiJSUzI1NiJ9.{payload}.sig")
+    }
+
+    #[test]
+    fn authorize_url() {
+        // the example from RFC 7636
+        assert_eq!(
+            super::code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+        assert_eq!(
+            client().authorize_url_for(&pending()),
+            "https://id.example.com/auth?prompt=login&response_type=code&client_id=cid&\
+            redirect_uri=https%3A%2F%2Fapp.example.com%2Fcb%2F&state=st&\
+            code_challenge=E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM&\
+            code_challenge_method=S256&scope=openid+email&nonce=n1"
+        );
+    }
+
+    #[test]
+    fn check_callback() {
+        let cb = |state: &str, code: Option<&str>, error: Option<&str>| super::Callback {
+            state: Some(state.to_string()),
+            code: code.map(|v| v.to_string()),
+            error: error.map(|v| v.to_string()),
+            error_description: None,
+        };
+        assert_eq!(
+            super::check_callback(&pending(), &cb("st", Some("c"), None)).unwrap(),
+            "c"
+        );
+        assert!(matches!(
+            super::check_callback(&pending(), &cb("other", Some("c"), None)),
+            Err(super::OAuth2Error::StateMismatch)
+        ));
+        assert!(matches!(
+            super::check_callback(&pending(), &cb("st", None, Some("access_denied"))),
+            Err(super::OAuth2Error::Provider { .. })
+        ));
+    }
+
+    // stand in for the token endpoint of the provider
+    fn token_endpoint(req: http::Request<bytes::Bytes>) -> http::Response<bytes::Bytes> {
+        let form: std::collections::HashMap<String, String> =
+            serde_urlencoded::from_bytes(req.body()).unwrap();
+        assert_eq!(req.uri(), "https://id.example.com/token");
+        assert_eq!(form["client_id"], "cid");
+        assert_eq!(form["client_secret"], "secret");
+
+        let body = match form["grant_type"].as_str() {
+            "authorization_code" if form["code"] == "good" => {
+                assert_eq!(form["code_verifier"], pending().verifier);
+                serde_json::json!({"access_token": "at", "refresh_token": "rt", "expires_in": 3600})
+            }
+            "refresh_token" if form["refresh_token"] == "rt" => {
+                serde_json::json!({"access_token": "at2"})
+            }
+            _ => {
+                return http::Response::builder()
+                    .status(400)
+                    .body(r#"{"error": "invalid_grant"}"#.into())
+                    .unwrap();
+            }
+        };
+        http::Response::new(body.to_string().into())
+    }
+
+    #[test]
+    fn token_exchange() {
+        let c = client();
+        let t = c
+            .exchange_code_with("good", &pending().verifier, token_endpoint)
+            .unwrap();
+        assert_eq!(t.access_token, "at");
+        assert_eq!(t.expires_in, Some(3600));
+
+        let t = c
+            .refresh_with(t.refresh_token.as_deref().unwrap(), token_endpoint)
+            .unwrap();
+        assert_eq!(t.access_token, "at2");
+
+        match c.exchange_code_with("bad", &pending().verifier, token_endpoint) {
+            Err(super::OAuth2Error::Provider { error, .. }) => assert_eq!(error, "invalid_grant"),
+            r => panic!("unexpected {r:?}"),
+        }
+    }
+
+    #[test]
+    fn userinfo() {
+        let info = client()
+            .userinfo_with("at", |req| {
+                assert_eq!(req.headers()["authorization"], "Bearer at");
+                http::Response::new(
+                    r#"{"sub": "42", "email": "a@example.com", "email_verified": true}"#.into(),
+                )
+            })
+            .unwrap();
+        let data = super::provider_data(&info);
+        assert_eq!(data.identity, "42");
+        assert_eq!(data.verified_emails, ["a@example.com"]);
+
+        // GitHub
+        let data = super::provider_data(&serde_json::json!({
+            "id": 7, "login": "octocat", "avatar_url": "https://a/", "email": "o@example.com"
+        }));
+        assert_eq!(data.identity, "7");
+        assert_eq!(data.username.as_deref(), Some("octocat"));
+        assert_eq!(data.emails, ["o@example.com"]);
+        assert!(data.verified_emails.is_empty());
+    }
+
+    #[test]
+    fn userinfo_subject() {
+        let authorization = |sub: &str| super::Authorization {
+            tokens: super::TokenResponse {
+                access_token: "at".to_string(),
+                token_type: None,
+                expires_in: None,
+                refresh_token: None,
+                scope: None,
+                id_token: None,
+            },
+            claims: Some(
+                super::decode_id_token(&id_token(serde_json::json!({
+                    "iss": "https://id.example.com", "sub": sub, "aud": "cid", "exp": 1000
+                })))
+                .unwrap(),
+            ),
+        };
+        let send = |_| http::Response::new(r#"{"sub": "42"}"#.into());
+
+        let data = client().provider_data_with(&authorization("42"), send);
+        assert_eq!(data.unwrap().identity, "42");
+        assert!(matches!(
+            client().provider_data_with(&authorization("43"), send),
+            Err(super::OAuth2Error::SubjectMismatch)
+        ));
+    }
+
+    #[test]
+    fn id_token_claims() {
+        let c = client();
+        let claims = |aud: serde_json::Value, exp: i64, nonce: &str| {
+            id_token(serde_json::json!({
+                "iss": "https://id.example.com", "sub": "42", "aud": aud, "exp": exp,
+                "nonce": nonce, "azp": "cid"
+            }))
+        };
+
+        let ok = claims("cid".into(), 1000, "n1");
+        assert_eq!(
+            c.validate_id_token_at(&ok, Some("n1"), 900).unwrap().sub,
+            "42"
+        );
+        let many = claims(serde_json::json!(["other", "cid"]), 1000, "n1");
+        assert!(c.validate_id_token_at(&many, Some("n1"), 900).is_ok());
+
+        for (token, nonce, now) in [
+            (ok.as_str(), Some("n2"), 900),
+            (ok.as_str(), Some("n1"), 2000),
+            (&claims("other".into(), 1000, "n1"), Some("n1"), 900),
+        ] {
+            assert!(matches!(
+                c.validate_id_token_at(token, nonce, now),
+                Err(super::OAuth2Error::InvalidIdToken(_))
+            ));
+        }
+        let other_issuer = c.clone().with_issuer("https://evil.example.com");
+        assert!(other_issuer.validate_id_token_at(&ok, None, 900).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Password hashing for auth providers that let users log in with a password.
+//!
+//! Passwords are hashed with argon2id, with a random salt from [ft_sdk::Rng], and stored as PHC
+//! strings, `$argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>`, so the parameters used are part of
+//! the stored hash. When the parameters are raised in a later version, [needs_rehash] tells which
+//! hashes are still using the old ones, so they can be replaced the next time the user logs in,
+//! which [verify_user_password] does.
+//!
+//! ```rust,ignore
+//! let hash = ft_sdk::auth::password::hash("correct horse battery staple")?;
+//! assert!(ft_sdk::auth::password::verify("correct horse battery staple", &hash)?);
+//! ```
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+
+/// Key in [ft_sdk::auth::ProviderData::custom] the hash is stored under by [set_user_password].
+pub const PASSWORD_HASH_KEY: &str = "hashed-password";
+
+#[derive(Debug, thiserror::Error)]
+pub enum PasswordError {
+    // password_hash::Error only implements std::error::Error with its `std` feature
+    #[error("password hash error: {0}")]
+    Hash(argon2::password_hash::Error),
+    #[error("user data error: {0}")]
+    UserData(#[from] ft_sdk::auth::UserDataError),
+    #[error("update user error: {0}")]
+    UpdateUser(#[from] ft_sdk::auth::provider::UpdateUserDataError),
+}
+
+impl From<argon2::password_hash::Error> for PasswordError {
+    fn from(e: argon2::password_hash::Error) -> Self {
+        PasswordError::Hash(e)
+    }
+}
+
+/// The hasher for new hashes, argon2id with the parameters recommended by OWASP.
+fn argon2() -> argon2::Argon2<'static> {
+    argon2::Argon2::new(
+        argon2::Algorithm::Argon2id,
+        argon2::Version::V0x13,
+        argon2::Params::DEFAULT,
+    )
+}
+
+/// Hash `password`, the returned PHC string is what should be stored.
+pub fn hash(password: &str) -> Result<String, PasswordError> {
+    let salt = SaltString::generate(&mut ft_sdk::Rng);
+    hash_with(&argon2(), password, &salt)
+}
+
+fn hash_with(
+    argon2: &argon2::Argon2,
+    password: &str,
+    salt: &SaltString,
+) -> Result<String, PasswordError> {
+    Ok(argon2.hash_password(password.as_bytes(), salt)?.to_string())
+}
+
+/// Check `password` against a hash created by [hash]. The hash is compared in constant time.
+///
+/// A hash that can not be parsed is an error, not a mismatch.
+pub fn verify(password: &str, hash: &str) -> Result<bool, PasswordError> {
+    let hash = PasswordHash::new(hash)?;
+    // the parameters are read from the hash, so this also verifies hashes made with old ones
+    match argon2().verify_password(password.as_bytes(), &hash) {
+        Ok(()) => Ok(true),
+        Err(argon2::password_hash::Error::Password) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Check if `hash` was made with a different algorithm or parameters than [hash] uses now, and
+/// should be replaced with a new hash of the password once it is known, that is, after the user
+/// logs in. Hashes that can not be parsed need a rehash too.
+pub fn needs_rehash(hash: &str) -> bool {
+    let hash = match PasswordHash::new(hash) {
+        Ok(v) => v,
+        Err(_) => return true,
+    };
+    let params = match argon2::Params::try_from(&hash) {
+        Ok(v) => v,
+        Err(_) => return true,
+    };
+    let current = argon2::Params::DEFAULT;
+    hash.algorithm != argon2::ARGON2ID_IDENT
+        || hash.version != Some(argon2::Version::V0x13.into())
+        || params.m_cost() != current.m_cost()
+        || params.t_cost() != current.t_cost()
+        || params.p_cost() != current.p_cost()
+}
+
+/// Hash `password` and store it in the `provider_id` data of the user, under
+/// [PASSWORD_HASH_KEY].
+pub fn set_user_password(
+    conn: &mut ft_sdk::Connection,
+    provider_id: &str,
+    user_id: &ft_sdk::UserId,
+    password: &str,
+) -> Result<(), PasswordError> {
+    let mut data = ft_sdk::auth::provider::user_data_by_id(conn, provider_id, user_id)?;
+    set_password_hash(&mut data, hash(password)?);
+    ft_sdk::auth::provider::update_user(conn, provider_id, user_id, data, false)?;
+    Ok(())
+}
+
+/// Find the user with `identity` for `provider_id`, and check `password` against the stored
+/// hash. `None` if there is no such user, the user has no password, or it does not match.
+///
+/// If the hash [needs_rehash], it is replaced with a new one.
+pub fn verify_user_password(
+    conn: &mut ft_sdk::Connection,
+    provider_id: &str,
+    identity: &str,
+    password: &str,
+) -> Result<Option<ft_sdk::UserId>, PasswordError> {
+    let (user_id, mut data) =
+        match ft_sdk::auth::provider::user_data_by_identity(conn, provider_id, identity) {
+            Ok(v) => v,
+            Err(ft_sdk::auth::UserDataError::NoDataFound) => {
+                // hash anyway, so the response time does not tell if the user exists
+                hash(password)?;
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+    let stored: String = match data.get_custom(PASSWORD_HASH_KEY) {
+        Some(v) => v,
+        None => {
+            // same for whether the user has a password
+            hash(password)?;
+            return Ok(None);
+        }
+    };
+    if !verify(password, &stored)? {
+        return Ok(None);
+    }
+
+    if needs_rehash(&stored) {
+        set_password_hash(&mut data, hash(password)?);
+        ft_sdk::auth::provider::update_user(conn, provider_id, &user_id, data, false)?;
+    }
+
+    Ok(Some(user_id))
+}
+
+fn set_password_hash(data: &mut ft_sdk::auth::ProviderData, hash: String) {
+    if !data.custom.is_object() {
+        data.custom = serde_json::json!({});
+    }
+    data.custom[PASSWORD_HASH_KEY] = hash.into();
+}
+
+#[cfg(test)]
+mod test {
+    // the default parameters are slow in debug builds, and the rng is not available in tests
+    fn weak_hash(password: &str) -> String {
+        let params = argon2::Params::new(64, 1, 1, None).unwrap();
+        let argon2 =
+            argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+        let salt = argon2::password_hash::SaltString::from_b64("c29tZXNhbHQ").unwrap();
+        super::hash_with(&argon2, password, &salt).unwrap()
+    }
+
+    #[test]
+    fn verify() {
+        let h = weak_hash("secret");
+        assert!(h.starts_with("$argon2id$v=19$m=64,t=1,p=1$"));
+        assert!(super::verify("secret", &h).unwrap());
+        assert!(!super::verify("Secret", &h).unwrap());
+        assert!(super::verify("secret", "not a hash").is_err());
+    }
+
+    #[test]
+    fn needs_rehash() {
+        assert!(super::needs_rehash(&weak_hash("secret")));
+        assert!(super::needs_rehash("secret"));
+        assert!(!super::needs_rehash(
+            "$argon2id$v=19$m=19456,t=2,p=1$c29tZXNhbHQ$\
+            4xzPSCrv0c0PIYjNQbwYoUuDLyqFQbuBVMrUrzqPzZM"
+        ));
+    }
+}
@@ -22,12 +22,7 @@
 //! username etc. The UI will have been provided by the auth provider, or some other generic auth
 //! setting package.
 
-/// In the current session, we have zero or more scopes dropped by different auth
-/// providers that have been used so far. Each auth provider sdk also provides some
-/// APIs that require certain scopes to be present. Before calling those APIs, the
-/// caller can check if the session has enough scopes to call that api. If not, the
-/// caller can request the user to log in again with the required scopes.
-pub struct Scope(pub String);
+pub use ft_sdk::auth::Scope;
 
 #[derive(Debug, thiserror::Error)]
 pub enum AuthError {
@@ -283,6 +278,7 @@ pub fn update_user(
                 .map_err(UpdateUserDataError::CantStoreUserData)
         }
     })?;
+    ft_sdk::auth::current::invalidate();
 
     Ok(())
 }
@@ -322,13 +318,21 @@ pub fn create_user(
 ///
 /// `identity`: Eg for GitHub, it could be the username. This is stored in the cookie so can be
 /// retrieved without a db call to show a user identifiable information.
+///
+/// An existing session is moved to a new id, see [ft_sdk::SessionID::rotate], so the returned id
+/// is always new. The session cookie is set to it if it is the session of the current request,
+/// else the caller has to set it. If the session belonged to no or another user, the auth state
+/// in it, [ft_sdk::auth::SESSION_AUTH_KEYS], is dropped.
+///
+/// The visitor's [ft_sdk::Tracker], if any, is linked to the user.
 pub fn login(
     conn: &mut ft_sdk::Connection,
     user_id: &ft_sdk::UserId,
     session_id: Option<ft_sdk::session::SessionID>,
 ) -> Result<ft_sdk::session::SessionID, LoginError> {
+    ft_sdk::analytics::link_current(conn, user_id);
     match session_id {
-        Some(session_id) => Ok(session_id.set_user_id(conn, user_id.clone())?),
+        Some(session_id) => Ok(session_id.rotate_for_user(conn, user_id)?),
         None => Ok(ft_sdk::session::SessionID::create(
             conn,
             Some(user_id.clone()),
@@ -337,6 +341,53 @@ pub fn login(
     }
 }
 
+/// Record that the user logged in to `session_id` using `provider_id`, as `identity`. This is
+/// what [ft_sdk::auth::session_providers] and [ft_sdk::auth::session_provider_ids] return.
+pub fn add_session_provider(
+    conn: &mut ft_sdk::Connection,
+    session_id: &ft_sdk::session::SessionID,
+    provider_id: &str,
+    identity: &str,
+) -> Result<(), LoginError> {
+    session_id.update_key(
+        conn,
+        ft_sdk::auth::SESSION_PROVIDERS_KEY,
+        |providers: Option<std::collections::BTreeMap<String, Vec<String>>>| {
+            let mut providers = providers.unwrap_or_default();
+            let ids = providers.entry(provider_id.to_string()).or_default();
+            if !ids.iter().any(|i| i == identity) {
+                ids.push(identity.to_string());
+            }
+            Some(providers)
+        },
+    )?;
+    Ok(())
+}
+
+/// Record that `provider` granted `scopes` to the session, in the session data under
+/// [ft_sdk::auth::SESSION_SCOPES_KEY]. Scopes granted earlier are kept, so logging in again
+/// with more scopes only adds to them.
+pub fn grant_scopes(
+    conn: &mut ft_sdk::Connection,
+    session_id: &ft_sdk::session::SessionID,
+    provider: &str,
+    scopes: &[Scope],
+) -> Result<(), LoginError> {
+    session_id.update_key(
+        conn,
+        ft_sdk::auth::SESSION_SCOPES_KEY,
+        |granted: Option<std::collections::BTreeMap<String, Vec<Scope>>>| {
+            let mut granted = granted.unwrap_or_default();
+            let list = granted.entry(provider.to_string()).or_default();
+            list.extend(scopes.iter().cloned());
+            list.sort();
+            list.dedup();
+            Some(granted)
+        },
+    )?;
+    Ok(())
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum LoginError {
     #[error("db error: {0}")]
@@ -345,6 +396,8 @@ pub enum LoginError {
     JsonError(#[from] serde_json::Error),
     #[error("session error: {0}")]
     SessionError(#[from] ft_sdk::Error),
+    #[error("session data error: {0}")]
+    SessionData(#[from] ft_sdk::session::SetKeyError),
 }
 
 // Normalise and save user details
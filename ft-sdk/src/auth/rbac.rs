@@ -0,0 +1,219 @@
+//! Roles and permissions of users, kept in the app's own database.
+//!
+//! A role is a named set of permissions, e.g. `editor` with `items.create` and `items.delete`.
+//! Users get permissions by being assigned roles. Roles belong to the app that defines them, the
+//! [ft_sdk::WasmPackage] of the current request, so apps sharing a database each have their
+//! own `admin` role:
+//!
+//! ```rust,ignore
+//! ft_sdk::auth::rbac::define_role(&mut conn, "editor", &["items.create", "items.delete"])?;
+//! ft_sdk::auth::rbac::assign_role(&mut conn, &user_id, "editor")?;
+//! assert!(ft_sdk::auth::rbac::user_has_permission(&mut conn, &user_id, "items.delete")?);
+//! ```
+//!
+//! Without a current app, outside a request or when the request does not name the package, the
+//! functions return [RbacError::NoCurrentApp] rather than share roles between apps.
+//!
+//! Handlers that need a permission take the `ft_sdk::RequirePermission` extractor.
+//!
+//! The tables have to be created by the app, add this migration to `FASTN.ftd`:
+//!
+//! ```ftd
+//! -- fastn.migration: fastn_role
+//!
+//! CREATE TABLE IF NOT EXISTS fastn_role
+//! (
+//!     id         INTEGER PRIMARY KEY,
+//!     app        TEXT NOT NULL,
+//!     name       TEXT NOT NULL,
+//!     created_at INTEGER NOT NULL,
+//!     UNIQUE (app, name)
+//! ) STRICT;
+//!
+//! CREATE TABLE IF NOT EXISTS fastn_role_permission
+//! (
+//!     role_id    INTEGER NOT NULL REFERENCES fastn_role (id) ON DELETE CASCADE,
+//!     permission TEXT NOT NULL,
+//!     PRIMARY KEY (role_id, permission)
+//! ) STRICT;
+//!
+//! CREATE TABLE IF NOT EXISTS fastn_user_role
+//! (
+//!     uid        INTEGER NOT NULL REFERENCES fastn_user (id) ON DELETE CASCADE,
+//!     role_id    INTEGER NOT NULL REFERENCES fastn_role (id) ON DELETE CASCADE,
+//!     created_at INTEGER NOT NULL,
+//!     PRIMARY KEY (uid, role_id)
+//! ) STRICT;
+//! ```
+
+#[derive(Debug, thiserror::Error)]
+pub enum RbacError {
+    #[error("db error: {0}")]
+    DatabaseError(#[from] diesel::result::Error),
+    #[error("role not found: {0}")]
+    RoleNotFound(String),
+    /// Roles are scoped to the [ft_sdk::WasmPackage] of the current request, and there is none,
+    /// e.g. the request has no `x-fastn-wasm-package` header.
+    #[error("no current app to scope roles to")]
+    NoCurrentApp,
+}
+
+/// Create the role `name` of the current app, or replace its permissions if it exists. Users
+/// assigned the role keep it.
+pub fn define_role(
+    conn: &mut ft_sdk::Connection,
+    name: &str,
+    permissions: &[&str],
+) -> Result<(), RbacError> {
+    use diesel::prelude::*;
+    use ft_sdk::auth::{fastn_role, fastn_role_permission};
+
+    conn.transaction(|conn| {
+        let role_id = match role_id(conn, name)? {
+            Some(v) => v,
+            None => diesel::insert_into(fastn_role::table)
+                .values((
+                    fastn_role::app.eq(current_app()?),
+                    fastn_role::name.eq(name),
+                    fastn_role::created_at.eq(ft_sdk::env::now()),
+                ))
+                .returning(fastn_role::id)
+                .get_result(conn)?,
+        };
+
+        diesel::delete(
+            fastn_role_permission::table.filter(fastn_role_permission::role_id.eq(role_id)),
+        )
+        .execute(conn)?;
+
+        let mut permissions = permissions.to_vec();
+        permissions.sort_unstable();
+        permissions.dedup();
+        for permission in permissions {
+            diesel::insert_into(fastn_role_permission::table)
+                .values((
+                    fastn_role_permission::role_id.eq(role_id),
+                    fastn_role_permission::permission.eq(permission),
+                ))
+                .execute(conn)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Give the user the role `name` of the current app, see [define_role]. Assigning a role the
+/// user already has does nothing.
+pub fn assign_role(
+    conn: &mut ft_sdk::Connection,
+    user_id: &ft_sdk::UserId,
+    name: &str,
+) -> Result<(), RbacError> {
+    use diesel::prelude::*;
+    use ft_sdk::auth::fastn_user_role;
+
+    conn.transaction(|conn| {
+        let role_id =
+            role_id(conn, name)?.ok_or_else(|| RbacError::RoleNotFound(name.to_string()))?;
+
+        let assigned: i64 = fastn_user_role::table
+            .filter(fastn_user_role::uid.eq(user_id.0))
+            .filter(fastn_user_role::role_id.eq(role_id))
+            .count()
+            .get_result(conn)?;
+        if assigned > 0 {
+            return Ok(());
+        }
+
+        diesel::insert_into(fastn_user_role::table)
+            .values((
+                fastn_user_role::uid.eq(user_id.0),
+                fastn_user_role::role_id.eq(role_id),
+                fastn_user_role::created_at.eq(ft_sdk::env::now()),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Take the role `name` of the current app away from the user. Revoking a role the user does
+/// not have does nothing, [RbacError::RoleNotFound] is returned if the role is not defined.
+pub fn revoke_role(
+    conn: &mut ft_sdk::Connection,
+    user_id: &ft_sdk::UserId,
+    name: &str,
+) -> Result<(), RbacError> {
+    use diesel::prelude::*;
+    use ft_sdk::auth::fastn_user_role;
+
+    let role_id = match role_id(conn, name)? {
+        Some(v) => v,
+        None => return Err(RbacError::RoleNotFound(name.to_string())),
+    };
+
+    diesel::delete(
+        fastn_user_role::table
+            .filter(fastn_user_role::uid.eq(user_id.0))
+            .filter(fastn_user_role::role_id.eq(role_id)),
+    )
+    .execute(conn)?;
+    Ok(())
+}
+
+/// The names of the roles of the current app the user has, sorted.
+pub fn user_roles(
+    conn: &mut ft_sdk::Connection,
+    user_id: &ft_sdk::UserId,
+) -> Result<Vec<String>, RbacError> {
+    use diesel::prelude::*;
+    use ft_sdk::auth::{fastn_role, fastn_user_role};
+
+    Ok(fastn_user_role::table
+        .inner_join(fastn_role::table)
+        .filter(fastn_user_role::uid.eq(user_id.0))
+        .filter(fastn_role::app.eq(current_app()?))
+        .select(fastn_role::name)
+        .order(fastn_role::name)
+        .load(conn)?)
+}
+
+/// Does any role of the current app the user has grant `permission`?
+pub fn user_has_permission(
+    conn: &mut ft_sdk::Connection,
+    user_id: &ft_sdk::UserId,
+    permission: &str,
+) -> Result<bool, RbacError> {
+    use diesel::prelude::*;
+    use ft_sdk::auth::{fastn_role, fastn_role_permission, fastn_user_role};
+
+    let count: i64 = fastn_user_role::table
+        .inner_join(fastn_role::table)
+        .inner_join(
+            fastn_role_permission::table
+                .on(fastn_role_permission::role_id.eq(fastn_user_role::role_id)),
+        )
+        .filter(fastn_user_role::uid.eq(user_id.0))
+        .filter(fastn_role::app.eq(current_app()?))
+        .filter(fastn_role_permission::permission.eq(permission))
+        .count()
+        .get_result(conn)?;
+    Ok(count > 0)
+}
+
+fn role_id(conn: &mut ft_sdk::Connection, name: &str) -> Result<Option<i64>, RbacError> {
+    use diesel::prelude::*;
+    use ft_sdk::auth::fastn_role;
+
+    Ok(fastn_role::table
+        .filter(fastn_role::app.eq(current_app()?))
+        .filter(fastn_role::name.eq(name))
+        .select(fastn_role::id)
+        .first(conn)
+        .optional()?)
+}
+
+fn current_app() -> Result<String, RbacError> {
+    ft_sdk::from_request::package::current_wasm_package()
+        .map(|p| p.0)
+        .ok_or(RbacError::NoCurrentApp)
+}
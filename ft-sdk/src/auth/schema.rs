@@ -13,5 +13,59 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+
+    fastn_auth_token (id) {
+        id -> Int8,
+        uid -> Int8,
+        purpose -> Text,
+        token_hash -> Text,
+        email -> Nullable<Text>,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::joinable!(fastn_session -> fastn_user (uid));
 diesel::allow_tables_to_appear_in_same_query!(fastn_user, fastn_session,);
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    fastn_role (id) {
+        id -> Int8,
+        app -> Text,
+        name -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    fastn_role_permission (role_id, permission) {
+        role_id -> Int8,
+        permission -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    fastn_user_role (uid, role_id) {
+        uid -> Int8,
+        role_id -> Int8,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::joinable!(fastn_role_permission -> fastn_role (role_id));
+diesel::joinable!(fastn_user_role -> fastn_role (role_id));
+diesel::joinable!(fastn_user_role -> fastn_user (uid));
+diesel::allow_tables_to_appear_in_same_query!(
+    fastn_user,
+    fastn_role,
+    fastn_role_permission,
+    fastn_user_role,
+);
@@ -0,0 +1,302 @@
+//! Single use tokens for email verification and password reset links.
+//!
+//! A token is a random string sent to the user, only its SHA-256 hash is stored, in the
+//! `fastn_auth_token` table, so a leaked database can not be used to verify emails or reset
+//! passwords. Tokens expire, and are deleted when used.
+//!
+//! The table has to be created by the app, add this migration to `FASTN.ftd`:
+//!
+//! ```ftd
+//! -- fastn.migration: fastn_auth_token
+//!
+//! CREATE TABLE IF NOT EXISTS fastn_auth_token
+//! (
+//!     id         INTEGER PRIMARY KEY,
+//!     uid        INTEGER NOT NULL REFERENCES fastn_user (id) ON DELETE CASCADE,
+//!     purpose    TEXT NOT NULL,
+//!     token_hash TEXT NOT NULL UNIQUE,
+//!     email      TEXT NULL,
+//!     expires_at INTEGER NOT NULL,
+//!     created_at INTEGER NOT NULL
+//! ) STRICT;
+//! ```
+//!
+//! A verification flow looks like this:
+//!
+//! ```rust,ignore
+//! let token = ft_sdk::auth::token::issue_verification_token(&mut conn, &user_id, &email)?;
+//! ft_sdk::auth::token::send_verification_email(from, &email, &token)?;
+//!
+//! // later, in the handler for the link in the email
+//! let (user_id, email) =
+//!     ft_sdk::auth::token::consume_verification_token(&mut conn, PROVIDER_ID, &token)?;
+//! ```
+
+/// How long a token from [issue_verification_token] is valid, one day.
+pub const VERIFICATION_TOKEN_TTL_SECONDS: i64 = 24 * 60 * 60;
+/// How long a token from [issue_password_reset_token] is valid, one hour.
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: i64 = 60 * 60;
+
+/// `mkind` of the email sent by [send_verification_email].
+pub const VERIFY_EMAIL_MKIND: &str = "auth.verify-email";
+/// `mkind` of the email sent by [send_password_reset_email].
+pub const RESET_PASSWORD_MKIND: &str = "auth.reset-password";
+
+const VERIFY_EMAIL: &str = "verify-email";
+const RESET_PASSWORD: &str = "reset-password";
+
+#[derive(Debug, thiserror::Error)]
+pub enum TokenError {
+    /// The token does not exist, was already used, or has expired. These are not told apart, so
+    /// tokens can not be probed.
+    #[error("token is invalid or expired")]
+    InvalidToken,
+    #[error("db error: {0}")]
+    DatabaseError(#[from] diesel::result::Error),
+    #[error("user data error: {0}")]
+    UserData(#[from] ft_sdk::auth::UserDataError),
+    #[error("update user error: {0}")]
+    UpdateUser(#[from] ft_sdk::auth::provider::UpdateUserDataError),
+    #[error("password error: {0}")]
+    Password(#[from] ft_sdk::auth::password::PasswordError),
+}
+
+/// Create a token to verify that `email` belongs to the user. Older unused tokens for the same
+/// email are deleted, so only the latest link works.
+pub fn issue_verification_token(
+    conn: &mut ft_sdk::Connection,
+    user_id: &ft_sdk::UserId,
+    email: &str,
+) -> Result<String, TokenError> {
+    issue(
+        conn,
+        user_id,
+        VERIFY_EMAIL,
+        Some(email),
+        VERIFICATION_TOKEN_TTL_SECONDS,
+    )
+}
+
+/// Use a token created by [issue_verification_token], and move the email from
+/// [ft_sdk::auth::ProviderData::emails] to [ft_sdk::auth::ProviderData::verified_emails] of the
+/// `provider_id` data of the user.
+pub fn consume_verification_token(
+    conn: &mut ft_sdk::Connection,
+    provider_id: &str,
+    token: &str,
+) -> Result<(ft_sdk::UserId, String), TokenError> {
+    use diesel::prelude::*;
+
+    conn.transaction(|conn| {
+        let (user_id, email) = consume(conn, VERIFY_EMAIL, token)?;
+        // verification tokens are always issued with an email
+        let email = email.ok_or(TokenError::InvalidToken)?;
+
+        let mut data = ft_sdk::auth::provider::user_data_by_id(conn, provider_id, &user_id)?;
+        mark_verified(&mut data, &email);
+        ft_sdk::auth::provider::update_user(conn, provider_id, &user_id, data, false)?;
+
+        Ok((user_id, email))
+    })
+}
+
+/// Create a token to reset the password of the user. Older unused reset tokens of the user are
+/// deleted.
+pub fn issue_password_reset_token(
+    conn: &mut ft_sdk::Connection,
+    user_id: &ft_sdk::UserId,
+) -> Result<String, TokenError> {
+    issue(
+        conn,
+        user_id,
+        RESET_PASSWORD,
+        None,
+        PASSWORD_RESET_TOKEN_TTL_SECONDS,
+    )
+}
+
+/// Use a token created by [issue_password_reset_token], and set the password of the user to
+/// `new_password`, see [ft_sdk::auth::password::set_user_password].
+pub fn consume_password_reset_token(
+    conn: &mut ft_sdk::Connection,
+    provider_id: &str,
+    token: &str,
+    new_password: &str,
+) -> Result<ft_sdk::UserId, TokenError> {
+    use diesel::prelude::*;
+
+    conn.transaction(|conn| {
+        let (user_id, _) = consume(conn, RESET_PASSWORD, token)?;
+        ft_sdk::auth::password::set_user_password(conn, provider_id, &user_id, new_password)?;
+        Ok(user_id)
+    })
+}
+
+/// Send the verification email, with mkind [VERIFY_EMAIL_MKIND]. The `token` and `email` are
+/// passed as context, for the template to build the link.
+pub fn send_verification_email(
+    from: ft_sdk::EmailAddress,
+    email: &str,
+    token: &str,
+) -> Result<ft_sdk::EmailHandle, ft_sdk::SendEmailError> {
+    send(from, email, token, VERIFY_EMAIL_MKIND)
+}
+
+/// Send the password reset email, with mkind [RESET_PASSWORD_MKIND]. The `token` and `email` are
+/// passed as context, for the template to build the link.
+pub fn send_password_reset_email(
+    from: ft_sdk::EmailAddress,
+    email: &str,
+    token: &str,
+) -> Result<ft_sdk::EmailHandle, ft_sdk::SendEmailError> {
+    send(from, email, token, RESET_PASSWORD_MKIND)
+}
+
+fn send(
+    from: ft_sdk::EmailAddress,
+    email: &str,
+    token: &str,
+    mkind: &str,
+) -> Result<ft_sdk::EmailHandle, ft_sdk::SendEmailError> {
+    let mut context = serde_json::Map::new();
+    context.insert("token".to_string(), token.into());
+    context.insert("email".to_string(), email.into());
+
+    let to = ft_sdk::EmailAddress {
+        name: None,
+        email: email.to_string(),
+    };
+    ft_sdk::email::send(&ft_sdk::Email::new(
+        from,
+        to,
+        mkind,
+        ft_sdk::EmailContent::FromMKind {
+            context: Some(context),
+        },
+    ))
+}
+
+fn issue(
+    conn: &mut ft_sdk::Connection,
+    user_id: &ft_sdk::UserId,
+    purpose: &str,
+    email: Option<&str>,
+    ttl_seconds: i64,
+) -> Result<String, TokenError> {
+    use diesel::prelude::*;
+    use ft_sdk::auth::fastn_auth_token;
+
+    let now = ft_sdk::env::now();
+    let token = ft_sdk::Rng::generate_key(32);
+
+    conn.transaction::<_, TokenError, _>(|conn| {
+        // expired tokens of everyone, and tokens this one replaces
+        diesel::delete(fastn_auth_token::table.filter(fastn_auth_token::expires_at.lt(now)))
+            .execute(conn)?;
+        let replaced = fastn_auth_token::table
+            .filter(fastn_auth_token::uid.eq(user_id.0))
+            .filter(fastn_auth_token::purpose.eq(purpose));
+        match email {
+            Some(email) => {
+                diesel::delete(replaced.filter(fastn_auth_token::email.eq(email))).execute(conn)?
+            }
+            None => diesel::delete(replaced).execute(conn)?,
+        };
+
+        diesel::insert_into(fastn_auth_token::table)
+            .values((
+                fastn_auth_token::uid.eq(user_id.0),
+                fastn_auth_token::purpose.eq(purpose),
+                fastn_auth_token::token_hash.eq(hash_token(&token)),
+                fastn_auth_token::email.eq(email),
+                fastn_auth_token::expires_at.eq(now + chrono::Duration::seconds(ttl_seconds)),
+                fastn_auth_token::created_at.eq(now),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })?;
+
+    Ok(token)
+}
+
+/// Delete the token, returning who it was issued to, if it is valid for `purpose`.
+fn consume(
+    conn: &mut ft_sdk::Connection,
+    purpose: &str,
+    token: &str,
+) -> Result<(ft_sdk::UserId, Option<String>), TokenError> {
+    use diesel::prelude::*;
+    use ft_sdk::auth::fastn_auth_token;
+
+    let (uid, email, expires_at) = conn.transaction::<_, TokenError, _>(|conn| {
+        let row: Option<(i64, i64, Option<String>, chrono::DateTime<chrono::Utc>)> =
+            fastn_auth_token::table
+                .select((
+                    fastn_auth_token::id,
+                    fastn_auth_token::uid,
+                    fastn_auth_token::email,
+                    fastn_auth_token::expires_at,
+                ))
+                .filter(fastn_auth_token::token_hash.eq(hash_token(token)))
+                .filter(fastn_auth_token::purpose.eq(purpose))
+                .first(conn)
+                .optional()?;
+
+        let (id, uid, email, expires_at) = row.ok_or(TokenError::InvalidToken)?;
+        // deleted even if expired, it is of no use any more. Only the request that deletes the
+        // row gets to use the token, a concurrent one may have read it too.
+        let deleted = diesel::delete(fastn_auth_token::table.filter(fastn_auth_token::id.eq(id)))
+            .execute(conn)?;
+        if deleted != 1 {
+            return Err(TokenError::InvalidToken);
+        }
+        Ok((uid, email, expires_at))
+    })?;
+
+    if expires_at < ft_sdk::env::now() {
+        return Err(TokenError::InvalidToken);
+    }
+
+    Ok((ft_sdk::UserId(uid), email))
+}
+
+/// Tokens are random, so a fast unsalted hash is enough, and lets the token be looked up by its
+/// hash.
+fn hash_token(token: &str) -> String {
+    use sha2::Digest;
+
+    sha2::Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn mark_verified(data: &mut ft_sdk::auth::ProviderData, email: &str) {
+    data.emails.retain(|e| e != email);
+    if !data.verified_emails.iter().any(|e| e == email) {
+        data.verified_emails.push(email.to_string());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn hash_token() {
+        assert_eq!(
+            super::hash_token("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn mark_verified() {
+        let mut data = ft_sdk::auth::ProviderData {
+            emails: vec!["a@example.com".to_string(), "b@example.com".to_string()],
+            ..Default::default()
+        };
+        super::mark_verified(&mut data, "a@example.com");
+        super::mark_verified(&mut data, "a@example.com");
+        assert_eq!(data.emails, ["b@example.com"]);
+        assert_eq!(data.verified_emails, ["a@example.com"]);
+    }
+}
@@ -0,0 +1,453 @@
+//! Time based one time passwords (TOTP, RFC 6238) as a second factor.
+//!
+//! Enrollment: generate a secret with [generate_secret], show [otpauth_uri] as a QR code, and
+//! call [enable] with a code from the authenticator app, to check it was set up correctly.
+//! [enable] returns recovery codes to show to the user, once, for when they lose the device.
+//!
+//! Login: once the first factor is checked, call [login_pending] instead of
+//! [ft_sdk::auth::provider::login] if [is_enabled]. The session is not logged in yet, see
+//! [ft_sdk::auth::TWO_FACTOR_PENDING_KEY], until [complete_login] is called with a valid code.
+//!
+//! The secret is stored encrypted, in the `custom` provider data of the user under [TOTP_KEY],
+//! along with the last used time step, so a code can not be used twice, and the SHA-256 hashes of
+//! the unused recovery codes.
+
+/// Key in [ft_sdk::auth::ProviderData::custom] the TOTP data is stored under.
+pub const TOTP_KEY: &str = "totp";
+/// Number of digits in a code.
+pub const DIGITS: u32 = 6;
+/// Seconds each code is valid for.
+pub const PERIOD: u64 = 30;
+/// Codes from this many periods before or after the current one are accepted, for clocks that
+/// are off, and users that are slow to type.
+pub const DEFAULT_DRIFT_STEPS: u64 = 1;
+/// Number of recovery codes [enable] creates.
+pub const RECOVERY_CODES: usize = 10;
+/// Codes [complete_login] checks for a pending login, after that the first factor has to be
+/// checked again.
+pub const MAX_LOGIN_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TotpError {
+    #[error("secret is not valid base32")]
+    InvalidSecret,
+    #[error("code does not match")]
+    InvalidCode,
+    #[error("two factor authentication is not enabled for the user")]
+    NotEnabled,
+    #[error("no login is waiting for the second factor")]
+    NoPendingLogin,
+    #[error("decryption error: {0}")]
+    Decryption(#[from] ft_sdk::DecryptionError),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("db error: {0}")]
+    Database(#[from] diesel::result::Error),
+    #[error("user data error: {0}")]
+    UserData(#[from] ft_sdk::auth::UserDataError),
+    #[error("update user error: {0}")]
+    UpdateUser(#[from] ft_sdk::auth::provider::UpdateUserDataError),
+    #[error("session error: {0}")]
+    Session(#[from] ft_sdk::session::SetKeyError),
+    #[error("login error: {0}")]
+    Login(#[from] ft_sdk::auth::provider::LoginError),
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+struct Stored {
+    /// The base32 secret, encrypted with [ft_sdk::EncryptedString].
+    secret: String,
+    /// The time step of the last accepted code.
+    last_step: u64,
+    /// SHA-256 of the unused recovery codes.
+    recovery_codes: Vec<String>,
+}
+
+/// A new random secret, base32 encoded, as authenticator apps expect it.
+pub fn generate_secret() -> String {
+    // 160 bits, the size of the SHA-1 output, as RFC 4226 recommends
+    let mut bytes = [0u8; 20];
+    rand_core::RngCore::fill_bytes(&mut ft_sdk::Rng, &mut bytes);
+    base32::encode(base32::Alphabet::Rfc4648 { padding: false }, &bytes)
+}
+
+/// The `otpauth://` URI to show as a QR code, so the secret can be added to an authenticator
+/// app. `issuer` is the name of the site, and `account` the name of the user on it, usually
+/// their email, both are shown in the app.
+pub fn otpauth_uri(secret: &str, issuer: &str, account: &str) -> String {
+    let label = format!("{issuer}:{account}");
+    // serializing string pairs can not fail
+    let query = serde_urlencoded::to_string([
+        ("secret", secret),
+        ("issuer", issuer),
+        ("algorithm", "SHA1"),
+        ("digits", &DIGITS.to_string()),
+        ("period", &PERIOD.to_string()),
+    ])
+    .unwrap_or_default();
+    format!("otpauth://totp/{}?{query}", encode_label(&label))
+}
+
+/// Percent-encode the label, which is a path, so spaces are `%20`, not `+`.
+fn encode_label(label: &str) -> String {
+    label
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// The code for the secret at `unix_time`.
+pub fn code_at(secret: &str, unix_time: u64) -> Result<String, TotpError> {
+    Ok(code_for_step(&decode_secret(secret)?, unix_time / PERIOD))
+}
+
+fn decode_secret(secret: &str) -> Result<Vec<u8>, TotpError> {
+    let secret = secret.replace(' ', "").to_ascii_uppercase();
+    base32::decode(base32::Alphabet::Rfc4648 { padding: false }, &secret)
+        .filter(|v| !v.is_empty())
+        .ok_or(TotpError::InvalidSecret)
+}
+
+/// HOTP (RFC 4226) of the time step.
+fn code_for_step(key: &[u8], step: u64) -> String {
+    use hmac::Mac;
+
+    let mut mac = hmac::Hmac::<sha1::Sha1>::new_from_slice(key).expect("hmac accepts any key");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let bin = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        bin % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    )
+}
+
+/// The time step `code` is valid for, if it is within `drift_steps` of `now` and after
+/// `last_step`. Every step in the window is checked, so the time taken does not tell which
+/// matched.
+fn matching_step(
+    key: &[u8],
+    code: &str,
+    now: u64,
+    drift_steps: u64,
+    last_step: Option<u64>,
+) -> Option<u64> {
+    let current = now / PERIOD;
+    let code = code.replace(' ', "");
+    let mut found = None;
+    for step in current.saturating_sub(drift_steps)..=current + drift_steps {
+        if ft_sdk::utils::constant_time_eq(&code_for_step(key, step), &code) && found.is_none() {
+            found = Some(step);
+        }
+    }
+    found.filter(|s| last_step.is_none_or(|l| *s > l))
+}
+
+/// Check `code` against `secret`, and if it is right, store the secret for the user
+/// along with new recovery codes, which are returned. Replaces the earlier secret, if any.
+pub fn enable(
+    conn: &mut ft_sdk::Connection,
+    provider_id: &str,
+    user_id: &ft_sdk::UserId,
+    secret: &str,
+    code: &str,
+) -> Result<Vec<String>, TotpError> {
+    let now = ft_sdk::env::now().timestamp() as u64;
+    let step = matching_step(
+        &decode_secret(secret)?,
+        code,
+        now,
+        DEFAULT_DRIFT_STEPS,
+        None,
+    )
+    .ok_or(TotpError::InvalidCode)?;
+
+    let codes: Vec<String> = (0..RECOVERY_CODES).map(|_| recovery_code()).collect();
+    let stored = Stored {
+        secret: ft_sdk::EncryptedString::from(ft_sdk::PlainText::from(secret)).to_string(),
+        last_step: step,
+        recovery_codes: codes.iter().map(|c| hash_recovery_code(c)).collect(),
+    };
+
+    let mut data = ft_sdk::auth::provider::user_data_by_id(conn, provider_id, user_id)?;
+    store(&mut data, Some(&stored))?;
+    ft_sdk::auth::provider::update_user(conn, provider_id, user_id, data, false)?;
+    Ok(codes)
+}
+
+/// Turn off the second factor for the user.
+pub fn disable(
+    conn: &mut ft_sdk::Connection,
+    provider_id: &str,
+    user_id: &ft_sdk::UserId,
+) -> Result<(), TotpError> {
+    let mut data = ft_sdk::auth::provider::user_data_by_id(conn, provider_id, user_id)?;
+    store(&mut data, None)?;
+    ft_sdk::auth::provider::update_user(conn, provider_id, user_id, data, false)?;
+    Ok(())
+}
+
+pub fn is_enabled(
+    conn: &mut ft_sdk::Connection,
+    provider_id: &str,
+    user_id: &ft_sdk::UserId,
+) -> Result<bool, TotpError> {
+    let data = ft_sdk::auth::provider::user_data_by_id(conn, provider_id, user_id)?;
+    Ok(data.get_custom::<Stored>(TOTP_KEY).is_some())
+}
+
+/// Check a code from the authenticator app, or an unused recovery code, for the user. Codes
+/// within `drift_steps` periods of now are accepted, see [DEFAULT_DRIFT_STEPS]. A code is only
+/// accepted once, and so is a recovery code.
+pub fn verify(
+    conn: &mut ft_sdk::Connection,
+    provider_id: &str,
+    user_id: &ft_sdk::UserId,
+    code: &str,
+    drift_steps: u64,
+) -> Result<bool, TotpError> {
+    use diesel::prelude::*;
+
+    // a concurrent verify of the same code waits, and then sees it used
+    conn.transaction(|conn| {
+        lock_user(conn, user_id)?;
+
+        let mut data = ft_sdk::auth::provider::user_data_by_id(conn, provider_id, user_id)?;
+        let mut stored: Stored = data.get_custom(TOTP_KEY).ok_or(TotpError::NotEnabled)?;
+
+        let plain: ft_sdk::PlainText =
+            ft_sdk::EncryptedString::from_already_encrypted_string(stored.secret.clone())
+                .try_into()?;
+        let key = decode_secret(&String::from(plain))?;
+
+        let now = ft_sdk::env::now().timestamp() as u64;
+        match matching_step(&key, code, now, drift_steps, Some(stored.last_step)) {
+            Some(step) => stored.last_step = step,
+            None => {
+                if !use_recovery_code(&mut stored, code) {
+                    return Ok(false);
+                }
+            }
+        }
+
+        store(&mut data, Some(&stored))?;
+        ft_sdk::auth::provider::update_user(conn, provider_id, user_id, data, false)?;
+        Ok(true)
+    })
+}
+
+/// Lock the user row until the end of the transaction, see `ft_sdk::session::sql::lock`.
+fn lock_user(
+    conn: &mut ft_sdk::Connection,
+    user_id: &ft_sdk::UserId,
+) -> Result<usize, diesel::result::Error> {
+    use diesel::prelude::*;
+    use ft_sdk::auth::fastn_user;
+
+    diesel::update(fastn_user::table.filter(fastn_user::id.eq(user_id.0)))
+        .set(fastn_user::updated_at.eq(fastn_user::updated_at))
+        .execute(conn)
+}
+
+/// The first factor of `user_id` is checked, and the second is needed before the session is
+/// logged in. A session is created if there is none.
+///
+/// The login waits [ft_sdk::auth::TWO_FACTOR_PENDING_TIMEOUT] seconds, and
+/// [MAX_LOGIN_ATTEMPTS] codes, for the second factor.
+pub fn login_pending(
+    conn: &mut ft_sdk::Connection,
+    user_id: &ft_sdk::UserId,
+    session_id: Option<ft_sdk::SessionID>,
+) -> Result<ft_sdk::SessionID, TotpError> {
+    let session_id = match session_id {
+        Some(v) => v,
+        None => ft_sdk::SessionID::create(conn, None, None)
+            .map_err(ft_sdk::auth::provider::LoginError::SessionError)?,
+    };
+    session_id.set_key(
+        conn,
+        ft_sdk::auth::TWO_FACTOR_PENDING_KEY,
+        ft_sdk::auth::TwoFactorPending {
+            uid: user_id.0,
+            at: ft_sdk::env::now().timestamp(),
+            attempts: 0,
+        },
+    )?;
+    Ok(session_id)
+}
+
+/// Check `code`, see [verify], for the user waiting in `session_id` after [login_pending], and
+/// if it is right, log the session in. Returns `None` if the code is wrong.
+///
+/// [TotpError::NoPendingLogin] is returned, and the pending login dropped, once it has expired or
+/// [MAX_LOGIN_ATTEMPTS] codes have been checked.
+///
+/// Logging in moves the session to a new id, which is returned, see
+/// [ft_sdk::auth::provider::login] for when the session cookie has to be set to it.
+pub fn complete_login(
+    conn: &mut ft_sdk::Connection,
+    provider_id: &str,
+    session_id: &ft_sdk::SessionID,
+    code: &str,
+    drift_steps: u64,
+) -> Result<Option<(ft_sdk::UserId, ft_sdk::SessionID)>, TotpError> {
+    // the attempt is counted before the code is checked, so concurrent requests can not get
+    // around the limit
+    let now = ft_sdk::env::now().timestamp();
+    let pending = session_id
+        .update_key(conn, ft_sdk::auth::TWO_FACTOR_PENDING_KEY, |p| {
+            next_attempt(p, now)
+        })?
+        .ok_or(TotpError::NoPendingLogin)?;
+    let user_id = ft_sdk::UserId(pending.uid);
+
+    if !verify(conn, provider_id, &user_id, code, drift_steps)? {
+        return Ok(None);
+    }
+
+    session_id.remove_key(conn, ft_sdk::auth::TWO_FACTOR_PENDING_KEY)?;
+    let session_id = ft_sdk::auth::provider::login(conn, &user_id, Some(session_id.clone()))?;
+    Ok(Some((user_id, session_id)))
+}
+
+/// The pending login with one more attempt counted, `None` if it has expired or has no attempts
+/// left.
+fn next_attempt(
+    pending: Option<ft_sdk::auth::TwoFactorPending>,
+    now: i64,
+) -> Option<ft_sdk::auth::TwoFactorPending> {
+    let mut pending = pending
+        .filter(|p| !p.is_expired(now))
+        .filter(|p| p.attempts < MAX_LOGIN_ATTEMPTS)?;
+    pending.attempts += 1;
+    Some(pending)
+}
+
+fn store(data: &mut ft_sdk::auth::ProviderData, stored: Option<&Stored>) -> Result<(), TotpError> {
+    if !data.custom.is_object() {
+        data.custom = serde_json::json!({});
+    }
+    data.custom[TOTP_KEY] = match stored {
+        Some(s) => serde_json::to_value(s)?,
+        None => serde_json::Value::Null,
+    };
+    Ok(())
+}
+
+/// A code like `k3j9x-7qm2p`, about 50 bits of randomness.
+fn recovery_code() -> String {
+    let alphabet = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let mut rng = ft_sdk::Rng;
+    let mut code: String = (0..10)
+        .map(|_| {
+            let i = rand_core::RngCore::next_u64(&mut rng) as usize % alphabet.len();
+            alphabet[i] as char
+        })
+        .collect();
+    code.insert(5, '-');
+    code
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    use sha2::Digest;
+
+    let code = code.trim().to_ascii_lowercase().replace([' ', '-'], "");
+    sha2::Sha256::digest(code.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Remove `code` from the unused recovery codes, `false` if it is not one of them.
+fn use_recovery_code(stored: &mut Stored, code: &str) -> bool {
+    let hash = hash_recovery_code(code);
+    let before = stored.recovery_codes.len();
+    stored
+        .recovery_codes
+        .retain(|c| !ft_sdk::utils::constant_time_eq(c, &hash));
+    stored.recovery_codes.len() != before
+}
+
+#[cfg(test)]
+mod test {
+    // "12345678901234567890", the secret of the RFC 6238 test vectors
+    const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn code_at() {
+        // the RFC has 8 digit codes, these are their last 6 digits
+        assert_eq!(super::code_at(SECRET, 59).unwrap(), "287082");
+        assert_eq!(super::code_at(SECRET, 1111111109).unwrap(), "081804");
+        assert_eq!(super::code_at(SECRET, 2000000000).unwrap(), "279037");
+        assert!(super::code_at("not base32!", 59).is_err());
+    }
+
+    #[test]
+    fn matching_step() {
+        let key = super::decode_secret(SECRET).unwrap();
+        // 59 is in step 1
+        assert_eq!(super::matching_step(&key, "287082", 59, 1, None), Some(1));
+        assert_eq!(super::matching_step(&key, "287082", 89, 1, None), Some(1));
+        assert_eq!(super::matching_step(&key, "287082", 89, 0, None), None);
+        // replay
+        assert_eq!(super::matching_step(&key, "287082", 59, 1, Some(1)), None);
+        assert_eq!(super::matching_step(&key, "000000", 59, 1, None), None);
+    }
+
+    #[test]
+    fn next_attempt() {
+        let pending = |at, attempts| ft_sdk::auth::TwoFactorPending {
+            uid: 1,
+            at,
+            attempts,
+        };
+
+        assert_eq!(super::next_attempt(None, 100), None);
+        assert_eq!(
+            super::next_attempt(Some(pending(100, 0)), 100),
+            Some(pending(100, 1))
+        );
+        assert_eq!(
+            super::next_attempt(Some(pending(100, 4)), 400),
+            Some(pending(100, 5))
+        );
+        // out of attempts
+        assert_eq!(super::next_attempt(Some(pending(100, 5)), 100), None);
+        // expired
+        assert_eq!(super::next_attempt(Some(pending(100, 0)), 401), None);
+    }
+
+    #[test]
+    fn otpauth_uri() {
+        assert_eq!(
+            super::otpauth_uri(SECRET, "Acme Co", "a@example.com"),
+            "otpauth://totp/Acme%20Co%3Aa%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&\
+            issuer=Acme+Co&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn recovery_codes() {
+        let mut stored = super::Stored {
+            secret: String::new(),
+            last_step: 0,
+            recovery_codes: vec![super::hash_recovery_code("k3j9x-7qm2p")],
+        };
+        assert!(!super::use_recovery_code(&mut stored, "aaaaa-bbbbb"));
+        assert!(super::use_recovery_code(&mut stored, "K3J9X 7QM2P"));
+        assert!(!super::use_recovery_code(&mut stored, "k3j9x-7qm2p"));
+    }
+}
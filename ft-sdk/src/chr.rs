@@ -15,12 +15,29 @@ impl<O> CHR<O> {
     }
 }
 
+thread_local! {
+    static PENDING_COOKIES: std::cell::RefCell<Vec<http::HeaderValue>> =
+        const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// Set a cookie on the response of the current request, for extractors, which do not see the
+/// response, e.g. [ft_sdk::Session]. Sent before the cookies of the handler's [CHR].
+pub(crate) fn add_pending_cookie<C: ft_sdk::cookie::IntoCookie>(c: C) {
+    PENDING_COOKIES.with(|p| p.borrow_mut().push(c.into_cookie()));
+}
+
+/// Drop the cookies left over by the previous request, called when the request is read.
+pub(crate) fn reset_pending_cookies() {
+    PENDING_COOKIES.with(|p| p.borrow_mut().clear());
+}
+
 pub(crate) fn chr(
     cookies: Vec<http::HeaderValue>,
     headers: Vec<(http::header::HeaderName, http::HeaderValue)>,
     mut response: http::Response<bytes::Bytes>,
 ) -> Result<http::Response<bytes::Bytes>, ft_sdk::Error> {
-    for cookie in cookies.into_iter() {
+    let pending = PENDING_COOKIES.with(|p| std::mem::take(&mut *p.borrow_mut()));
+    for cookie in pending.into_iter().chain(cookies) {
         response
             .headers_mut()
             .try_append(http::header::SET_COOKIE, cookie)?;
@@ -33,11 +50,38 @@ pub(crate) fn chr(
 }
 
 impl<O> CHR<O> {
-    pub fn with_cookie(mut self, c: http::HeaderValue) -> Self {
-        self.cookies.push(c);
+    /// Set a cookie, see [ft_sdk::cookie] for what can be passed.
+    pub fn with_cookie<C: ft_sdk::cookie::IntoCookie>(mut self, c: C) -> Self {
+        self.cookies.push(c.into_cookie());
         self
     }
 
+    /// Set a cookie holding `value`, signed so changes can be detected, see
+    /// [ft_sdk::cookie::SetCookie::signed]. Read it using [ft_sdk::SignedCookie].
+    pub fn with_signed_cookie<N: AsRef<str>, T: serde::Serialize>(
+        self,
+        name: N,
+        value: &T,
+    ) -> Result<Self, ft_sdk::Error> {
+        Ok(self.with_cookie(ft_sdk::cookie::SetCookie::signed(name, value)?))
+    }
+
+    /// Set a cookie holding `value`, encrypted, see [ft_sdk::cookie::SetCookie::encrypted]. Read
+    /// it using [ft_sdk::EncryptedCookie].
+    pub fn with_encrypted_cookie<N: AsRef<str>, T: serde::Serialize>(
+        self,
+        name: N,
+        value: &T,
+    ) -> Result<Self, ft_sdk::Error> {
+        Ok(self.with_cookie(ft_sdk::cookie::SetCookie::encrypted(name, value)?))
+    }
+
+    /// Ask the browser to delete the cookie `name`. Use [ft_sdk::cookie::SetCookie::removal] if
+    /// the cookie was set with a domain or path.
+    pub fn remove_cookie<N: AsRef<str>>(self, name: N) -> Self {
+        self.with_cookie(ft_sdk::cookie::SetCookie::removal(name))
+    }
+
     pub fn with_header(mut self, key: http::HeaderName, value: http::HeaderValue) -> Self {
         self.headers.push((key, value));
         self
@@ -61,83 +105,101 @@ mod test {
         );
     }
 
-    // #[test]
-    // fn cookie() {
-    //     let r = ft_sdk::json(()).unwrap();
-    //     let chr = super::CHR::new(()).with_cookie(("name", "value"));
-    //     let r = super::chr(chr.cookies, chr.headers, r).unwrap();
-    //
-    //     let cookies = r.headers().get_all(http::header::SET_COOKIE);
-    //     let mut iter = cookies.iter();
-    //     assert_eq!(
-    //         iter.next(),
-    //         Some(&http::HeaderValue::from_static(
-    //             "name=value; Secure; HttpOnly; SameSite=Strict; Max-Age=34560000"
-    //         ))
-    //     );
-    //     assert_eq!(iter.next(), None);
-    //
-    //     let r = ft_sdk::json(()).unwrap();
-    //     let chr = super::CHR::new(()).with_cookie(("name", "value", 200));
-    //     let r = super::chr(chr.cookies, chr.headers, r).unwrap();
-    //
-    //     let cookies = r.headers().get_all(http::header::SET_COOKIE);
-    //     let mut iter = cookies.iter();
-    //     assert_eq!(
-    //         iter.next(),
-    //         Some(&http::HeaderValue::from_static(
-    //             "name=value; Secure; HttpOnly; SameSite=Strict; Max-Age=200"
-    //         ))
-    //     );
-    //     assert_eq!(iter.next(), None);
-    //
-    //     let r = ft_sdk::json(()).unwrap();
-    //     let chr = super::CHR::new(())
-    //         .with_cookie(("name", "value"))
-    //         .with_cookie(("n2", "v2"));
-    //     let r = super::chr(chr.cookies, chr.headers, r).unwrap();
-    //
-    //     let cookies = r.headers().get_all(http::header::SET_COOKIE);
-    //     let mut iter = cookies.iter();
-    //     assert_eq!(
-    //         iter.next(),
-    //         Some(&http::HeaderValue::from_static(
-    //             "name=value; Secure; HttpOnly; SameSite=Strict; Max-Age=34560000"
-    //         ))
-    //     );
-    //     assert_eq!(
-    //         iter.next(),
-    //         Some(&http::HeaderValue::from_static(
-    //             "n2=v2; Secure; HttpOnly; SameSite=Strict; Max-Age=34560000"
-    //         ))
-    //     );
-    // }
-    //
-    // #[test]
-    // fn raw_cookie() {
-    //     let r = ft_sdk::json(()).unwrap();
-    //     let chr = super::CHR::new(()).with_cookie(http::HeaderValue::from_static("hello"));
-    //     let r = super::chr(chr.cookies, chr.headers, r).unwrap();
-    //
-    //     let cookies = r.headers().get_all(http::header::SET_COOKIE);
-    //     let mut iter = cookies.iter();
-    //     assert_eq!(iter.next(), Some(&http::HeaderValue::from_static("hello")));
-    //     assert_eq!(iter.next(), None);
-    //
-    //     let r = ft_sdk::json(()).unwrap();
-    //     let chr = super::CHR::new(())
-    //         .with_cookie(http::HeaderValue::from_static("hello"))
-    //         .with_cookie(http::HeaderValue::from_static("hello"));
-    //     let r = super::chr(chr.cookies, chr.headers, r).unwrap();
-    //
-    //     let cookies = r.headers().get_all(http::header::SET_COOKIE);
-    //     let mut iter = cookies.iter();
-    //     assert_eq!(
-    //         iter.next(),
-    //         Some(&http::HeaderValue::from_static(
-    //             "name=value; Secure; HttpOnly; SameSite=Strict; Max-Age=34560000"
-    //         ))
-    //     );
-    //     assert_eq!(iter.next(), Some(&http::HeaderValue::from_static("hello")));
-    // }
+    #[test]
+    fn cookie() {
+        let r = ft_sdk::json(()).unwrap();
+        let chr = super::CHR::new(()).with_cookie(("name", "value"));
+        let r = super::chr(chr.cookies, chr.headers, r).unwrap();
+
+        let cookies = r.headers().get_all(http::header::SET_COOKIE);
+        let mut iter = cookies.iter();
+        assert_eq!(
+            iter.next(),
+            Some(&http::HeaderValue::from_static(
+                "name=value; Secure; HttpOnly; SameSite=Strict; Max-Age=34560000"
+            ))
+        );
+        assert_eq!(iter.next(), None);
+
+        let r = ft_sdk::json(()).unwrap();
+        let chr = super::CHR::new(()).with_cookie(("name", "value", 200));
+        let r = super::chr(chr.cookies, chr.headers, r).unwrap();
+
+        let cookies = r.headers().get_all(http::header::SET_COOKIE);
+        let mut iter = cookies.iter();
+        assert_eq!(
+            iter.next(),
+            Some(&http::HeaderValue::from_static(
+                "name=value; Secure; HttpOnly; SameSite=Strict; Max-Age=200"
+            ))
+        );
+        assert_eq!(iter.next(), None);
+
+        let r = ft_sdk::json(()).unwrap();
+        let chr = super::CHR::new(())
+            .with_cookie(("name", "value"))
+            .with_cookie(("n2", "v2"));
+        let r = super::chr(chr.cookies, chr.headers, r).unwrap();
+
+        let cookies = r.headers().get_all(http::header::SET_COOKIE);
+        let mut iter = cookies.iter();
+        assert_eq!(
+            iter.next(),
+            Some(&http::HeaderValue::from_static(
+                "name=value; Secure; HttpOnly; SameSite=Strict; Max-Age=34560000"
+            ))
+        );
+        assert_eq!(
+            iter.next(),
+            Some(&http::HeaderValue::from_static(
+                "n2=v2; Secure; HttpOnly; SameSite=Strict; Max-Age=34560000"
+            ))
+        );
+    }
+
+    #[test]
+    fn pending_cookie() {
+        super::add_pending_cookie(("fastn-sid", "s1"));
+        let r = ft_sdk::json(()).unwrap();
+        let chr = super::CHR::new(()).with_cookie(("name", "value"));
+        let r = super::chr(chr.cookies, chr.headers, r).unwrap();
+
+        let cookies: Vec<_> = r
+            .headers()
+            .get_all(http::header::SET_COOKIE)
+            .iter()
+            .collect();
+        assert_eq!(cookies.len(), 2);
+        assert!(cookies[0].to_str().unwrap().starts_with("fastn-sid=s1;"));
+        assert!(cookies[1].to_str().unwrap().starts_with("name=value;"));
+
+        // sent once
+        let r = ft_sdk::json(()).unwrap();
+        let r = super::chr(vec![], vec![], r).unwrap();
+        assert!(r.headers().get(http::header::SET_COOKIE).is_none());
+    }
+
+    #[test]
+    fn raw_cookie() {
+        let r = ft_sdk::json(()).unwrap();
+        let chr = super::CHR::new(()).with_cookie(http::HeaderValue::from_static("hello"));
+        let r = super::chr(chr.cookies, chr.headers, r).unwrap();
+
+        let cookies = r.headers().get_all(http::header::SET_COOKIE);
+        let mut iter = cookies.iter();
+        assert_eq!(iter.next(), Some(&http::HeaderValue::from_static("hello")));
+        assert_eq!(iter.next(), None);
+
+        let r = ft_sdk::json(()).unwrap();
+        let chr = super::CHR::new(())
+            .with_cookie(http::HeaderValue::from_static("hello"))
+            .with_cookie(http::HeaderValue::from_static("hello"));
+        let r = super::chr(chr.cookies, chr.headers, r).unwrap();
+
+        let cookies = r.headers().get_all(http::header::SET_COOKIE);
+        let mut iter = cookies.iter();
+        assert_eq!(iter.next(), Some(&http::HeaderValue::from_static("hello")));
+        assert_eq!(iter.next(), Some(&http::HeaderValue::from_static("hello")));
+        assert_eq!(iter.next(), None);
+    }
 }
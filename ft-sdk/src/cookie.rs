@@ -0,0 +1,327 @@
+//! Build `Set-Cookie` headers.
+//!
+//! ```rust
+//! # fn main() -> Result<(), ft_sdk::Error> {
+//! let resp = ft_sdk::data::json("ok")?
+//!     // `name=value; Secure; HttpOnly; SameSite=Strict; Max-Age=34560000`
+//!     .with_cookie(("name", "value"))
+//!     // same, but with `Max-Age=3600`
+//!     .with_cookie(("short", "lived", 3600))
+//!     .with_cookie(
+//!         ft_sdk::cookie::SetCookie::new("theme", "dark mode")
+//!             .with_http_only(false)
+//!             .with_same_site(ft_sdk::cookie::SameSite::Lax)
+//!             .with_path("/"),
+//!     )
+//!     .remove_cookie("old");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Values are percent-encoded, and [ft_sdk::Cookie] decodes them, so any string can be stored.
+//!
+//! To store values the user must not be able to change, use [SetCookie::signed], or
+//! [SetCookie::encrypted] if the user must not be able to read them either, and read them using
+//! [ft_sdk::SignedCookie] and [ft_sdk::EncryptedCookie]. Both use the key managed by the host.
+
+/// Max-Age used unless another is set, about 400 days, which is the most browsers allow.
+pub const DEFAULT_MAX_AGE: i64 = 34560000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    /// Send the cookie with cross site requests too, browsers require `Secure` for this.
+    None,
+}
+
+/// A cookie to set, with secure defaults: `Secure`, `HttpOnly`, `SameSite=Strict` and
+/// [DEFAULT_MAX_AGE].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetCookie {
+    name: String,
+    value: String,
+    max_age: Option<i64>,
+    expires: Option<chrono::DateTime<chrono::Utc>>,
+    domain: Option<String>,
+    path: Option<String>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+impl SetCookie {
+    pub fn new<N: AsRef<str>, V: AsRef<str>>(name: N, value: V) -> SetCookie {
+        SetCookie {
+            name: name.as_ref().to_string(),
+            value: value.as_ref().to_string(),
+            max_age: Some(DEFAULT_MAX_AGE),
+            expires: None,
+            domain: None,
+            path: None,
+            secure: true,
+            http_only: true,
+            same_site: Some(SameSite::Strict),
+        }
+    }
+
+    /// A cookie holding `value` serialized as JSON, along with a signature so changes to it can be
+    /// detected. The value is readable by the user, see [SetCookie::encrypted] if it must not be.
+    pub fn signed<N: AsRef<str>, T: serde::Serialize>(
+        name: N,
+        value: &T,
+    ) -> Result<SetCookie, ft_sdk::Error> {
+        let json = serde_json::to_string(value)?;
+        let v = sign(name.as_ref(), &json, ft_sys::encrypt);
+        Ok(SetCookie::new(name, v))
+    }
+
+    /// A cookie holding `value` serialized as JSON and encrypted.
+    pub fn encrypted<N: AsRef<str>, T: serde::Serialize>(
+        name: N,
+        value: &T,
+    ) -> Result<SetCookie, ft_sdk::Error> {
+        let json = serde_json::to_string(value)?;
+        let v = encrypt(name.as_ref(), &json, ft_sys::encrypt);
+        Ok(SetCookie::new(name, v))
+    }
+
+    /// A cookie that tells the browser to delete the cookie `name`. If the cookie was set with a
+    /// domain or path, the same domain and path must be set on this one too.
+    pub fn removal<N: AsRef<str>>(name: N) -> SetCookie {
+        SetCookie::new(name, "")
+            .with_max_age(0)
+            .with_expires(chrono::DateTime::UNIX_EPOCH)
+    }
+
+    /// Lifetime of the cookie in seconds.
+    pub fn with_max_age(mut self, seconds: i64) -> SetCookie {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Remove `Max-Age`, so the cookie is deleted when the browser is closed, unless
+    /// [SetCookie::with_expires] is used.
+    pub fn session(mut self) -> SetCookie {
+        self.max_age = None;
+        self
+    }
+
+    pub fn with_expires(mut self, expires: chrono::DateTime<chrono::Utc>) -> SetCookie {
+        self.expires = Some(expires);
+        self
+    }
+
+    pub fn with_domain<S: AsRef<str>>(mut self, domain: S) -> SetCookie {
+        self.domain = Some(domain.as_ref().to_string());
+        self
+    }
+
+    pub fn with_path<S: AsRef<str>>(mut self, path: S) -> SetCookie {
+        self.path = Some(path.as_ref().to_string());
+        self
+    }
+
+    pub fn with_secure(mut self, secure: bool) -> SetCookie {
+        self.secure = secure;
+        self
+    }
+
+    pub fn with_http_only(mut self, http_only: bool) -> SetCookie {
+        self.http_only = http_only;
+        self
+    }
+
+    /// `None` leaves out the `SameSite` attribute, so the browser default applies.
+    pub fn with_same_site<S: Into<Option<SameSite>>>(mut self, same_site: S) -> SetCookie {
+        self.same_site = same_site.into();
+        self
+    }
+}
+
+impl std::fmt::Display for SetCookie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", encode(&self.name), encode(&self.value))?;
+        if self.secure {
+            f.write_str("; Secure")?;
+        }
+        if self.http_only {
+            f.write_str("; HttpOnly")?;
+        }
+        match self.same_site {
+            Some(SameSite::Strict) => f.write_str("; SameSite=Strict")?,
+            Some(SameSite::Lax) => f.write_str("; SameSite=Lax")?,
+            Some(SameSite::None) => f.write_str("; SameSite=None")?,
+            None => {}
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={max_age}")?;
+        }
+        if let Some(ref expires) = self.expires {
+            write!(
+                f,
+                "; Expires={}",
+                expires.format("%a, %d %b %Y %H:%M:%S GMT")
+            )?;
+        }
+        if let Some(ref domain) = self.domain {
+            write!(f, "; Domain={}", encode(domain))?;
+        }
+        if let Some(ref path) = self.path {
+            write!(f, "; Path={}", encode(path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Percent-encode everything that is not allowed in a cookie value, and `%` itself.
+fn encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'!' | b'#'..=b'$' | b'&'..=b'+' | b'-'..=b':' | b'<'..=b'[' | b']'..=b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+// The version is part of the value so the format, or the key, can be changed later while still
+// reading old cookies. The cookie name is part of what is signed or encrypted, so a value can not
+// be moved to a different cookie.
+const SIGNED_V1: &str = "s1";
+const ENCRYPTED_V1: &str = "e1";
+
+fn sign(name: &str, json: &str, encrypt: impl Fn(&str) -> String) -> String {
+    // `.` is escaped in the json so the tag, which may contain anything, can follow it
+    let escaped = json.replace('%', "%25").replace('.', "%2E");
+    format!(
+        "{SIGNED_V1}.{escaped}.{}",
+        encrypt(&format!("{name}:{json}"))
+    )
+}
+
+fn encrypt(name: &str, json: &str, encrypt: impl Fn(&str) -> String) -> String {
+    format!("{ENCRYPTED_V1}.{}", encrypt(&format!("{name}:{json}")))
+}
+
+/// Read the value of a cookie created by [SetCookie::signed]. `None` if the signature does not
+/// match.
+#[cfg_attr(not(feature = "field-extractors"), allow(dead_code))]
+pub(crate) fn verify<T: serde::de::DeserializeOwned>(
+    name: &str,
+    value: &str,
+    decrypt: impl Fn(&str) -> Result<String, ft_sdk::DecryptionError>,
+) -> Option<T> {
+    let (escaped, tag) = value
+        .strip_prefix(SIGNED_V1)?
+        .strip_prefix('.')?
+        .split_once('.')?;
+    let json = ft_sdk::utils::percent_decode(escaped)?;
+    if decrypt(tag).ok()? != format!("{name}:{json}") {
+        return None;
+    }
+    serde_json::from_str(&json).ok()
+}
+
+/// Read the value of a cookie created by [SetCookie::encrypted]. `None` if it can not be
+/// decrypted.
+#[cfg_attr(not(feature = "field-extractors"), allow(dead_code))]
+pub(crate) fn decrypt<T: serde::de::DeserializeOwned>(
+    name: &str,
+    value: &str,
+    decrypt: impl Fn(&str) -> Result<String, ft_sdk::DecryptionError>,
+) -> Option<T> {
+    let encrypted = value.strip_prefix(ENCRYPTED_V1)?.strip_prefix('.')?;
+    let plain = decrypt(encrypted).ok()?;
+    serde_json::from_str(plain.strip_prefix(name)?.strip_prefix(':')?).ok()
+}
+
+/// Things that can be passed to [ft_sdk::chr::CHR::with_cookie].
+///
+/// - [SetCookie]
+/// - `(name, value)`, using the defaults of [SetCookie::new]
+/// - `(name, value, max_age)`, same, with `Max-Age` in seconds
+/// - [http::HeaderValue], used as is
+pub trait IntoCookie {
+    fn into_cookie(self) -> http::HeaderValue;
+}
+
+impl IntoCookie for http::HeaderValue {
+    fn into_cookie(self) -> http::HeaderValue {
+        self
+    }
+}
+
+impl IntoCookie for SetCookie {
+    fn into_cookie(self) -> http::HeaderValue {
+        // everything user provided is percent-encoded, so this is always valid
+        http::HeaderValue::from_str(&self.to_string()).expect("cookie is a valid header value")
+    }
+}
+
+impl<N: AsRef<str>, V: AsRef<str>> IntoCookie for (N, V) {
+    fn into_cookie(self) -> http::HeaderValue {
+        SetCookie::new(self.0, self.1).into_cookie()
+    }
+}
+
+impl<N: AsRef<str>, V: AsRef<str>> IntoCookie for (N, V, i64) {
+    fn into_cookie(self) -> http::HeaderValue {
+        SetCookie::new(self.0, self.1)
+            .with_max_age(self.2)
+            .into_cookie()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn set_cookie() {
+        assert_eq!(
+            super::SetCookie::new("a b", "x;y=%")
+                .with_same_site(None)
+                .with_secure(false)
+                .session()
+                .with_path("/")
+                .to_string(),
+            "a%20b=x%3By=%25; HttpOnly; Path=/"
+        );
+        assert_eq!(
+            super::SetCookie::removal("n").to_string(),
+            "n=; Secure; HttpOnly; SameSite=Strict; Max-Age=0; \
+            Expires=Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+    }
+
+    // stand in for the host encryption, which is not available in tests
+    fn enc(s: &str) -> String {
+        s.chars().rev().collect()
+    }
+
+    fn dec(s: &str) -> Result<String, ft_sdk::DecryptionError> {
+        Ok(s.chars().rev().collect())
+    }
+
+    #[test]
+    fn signed() {
+        let v = super::sign("a", "[1,\"x.y\"]", enc);
+        assert_eq!(
+            super::verify::<(i32, String)>("a", &v, dec),
+            Some((1, "x.y".to_string()))
+        );
+        assert_eq!(super::verify::<(i32, String)>("b", &v, dec), None);
+        let tampered = v.replacen("[1,", "[2,", 1);
+        assert_eq!(super::verify::<(i32, String)>("a", &tampered, dec), None);
+    }
+
+    #[test]
+    fn encrypted() {
+        let v = super::encrypt("a", "42", enc);
+        assert_eq!(super::decrypt::<i32>("a", &v, dec), Some(42));
+        assert_eq!(super::decrypt::<i32>("b", &v, dec), None);
+        assert_eq!(super::decrypt::<i32>("a", "e2.24:a", dec), None);
+    }
+}
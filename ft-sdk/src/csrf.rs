@@ -0,0 +1,170 @@
+//! Protection against cross site request forgery (CSRF).
+//!
+//! Each session gets a random token, stored in the session data under [CSRF_KEY]. Pages embed
+//! the token, read using the [CsrfToken] extractor, in forms they render, and form handlers
+//! check it is sent back, either in the [CSRF_FIELD] field of the body, or in the [CSRF_HEADER]
+//! header.
+//!
+//! The check is enabled on a handler using the `csrf` argument:
+//!
+//! ```rust,ignore
+//! #[ft_sdk::form(csrf)]
+//! fn update_profile(name: ft_sdk::Required<"name">) -> ft_sdk::form::Result {
+//!     ft_sdk::form::reload()
+//! }
+//! ```
+//!
+//! which is the same as adding the [Csrf] middleware first, and is how it is used with
+//! `middleware = SOME_CONST` or [ft_sdk::Router::with_middleware].
+//!
+//! Requests without the session cookie are not checked, as there is no session a forged request
+//! could act as. `GET`, `HEAD` and `OPTIONS` requests are not checked either, they should not
+//! change anything.
+
+/// Key in the session data the token is stored under.
+pub const CSRF_KEY: &str = "csrf-token";
+/// Request body field the token is read from.
+pub const CSRF_FIELD: &str = "csrf-token";
+/// Header the token is read from, for requests made from JavaScript.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Get the token for `session`, creating it if the session does not have one yet.
+pub fn token(
+    conn: &mut ft_sdk::Connection,
+    session: &ft_sdk::SessionID,
+) -> Result<String, ft_sdk::Error> {
+    if let Some(t) = session.data(conn)?.get_key(CSRF_KEY) {
+        return Ok(t);
+    }
+
+    let t = ft_sdk::Rng::generate_key(32);
+    session.set_key(conn, CSRF_KEY, &t)?;
+    Ok(t)
+}
+
+/// The CSRF token of the current session, `None` if there is no session.
+#[derive(Clone, Debug)]
+pub struct CsrfToken(pub Option<String>);
+
+impl ft_sdk::FromRequest for CsrfToken {
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        let session = match session(req) {
+            Some(v) => v,
+            None => return Ok(CsrfToken(None)),
+        };
+        ft_sdk::from_request::connection::with(|conn| match token(conn, &session) {
+            Ok(t) => Ok(CsrfToken(Some(t))),
+            // the session cookie is stale, the session is not in the db any more
+            Err(e) if is_not_found(&e) => Ok(CsrfToken(None)),
+            Err(e) => Err(e),
+        })
+    }
+}
+
+/// Middleware that rejects requests without the right CSRF token with
+/// [ft_sdk::SpecialError::InvalidCsrfToken].
+pub struct Csrf;
+
+impl ft_sdk::Middleware for Csrf {
+    fn before(
+        &self,
+        req: &mut http::Request<serde_json::Value>,
+    ) -> ft_sdk::Result<Option<http::Response<bytes::Bytes>>> {
+        verify(req)?;
+        Ok(None)
+    }
+}
+
+/// Check the CSRF token sent with `req`, see the module documentation for when it is checked.
+pub fn verify(req: &http::Request<serde_json::Value>) -> Result<(), ft_sdk::Error> {
+    if is_safe(req.method()) {
+        return Ok(());
+    }
+    let session = match session(req) {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    let expected: Option<Option<String>> =
+        ft_sdk::from_request::connection::with(|conn| match session.data(conn) {
+            Ok(d) => Ok(Some(d.get_key(CSRF_KEY))),
+            Err(diesel::result::Error::NotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        })?;
+    let expected = match expected {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    if matches(expected.as_deref(), sent(req).as_deref()) {
+        Ok(())
+    } else {
+        Err(ft_sdk::SpecialError::InvalidCsrfToken.into())
+    }
+}
+
+fn session(req: &http::Request<serde_json::Value>) -> Option<ft_sdk::SessionID> {
+    ft_sdk::from_request::fields::cookie(ft_sdk::auth::SESSION_KEY, req)
+        .filter(|v| !v.is_empty())
+        .map(ft_sdk::SessionID)
+}
+
+fn is_not_found(e: &ft_sdk::Error) -> bool {
+    matches!(
+        e.downcast_ref::<diesel::result::Error>(),
+        Some(diesel::result::Error::NotFound)
+    )
+}
+
+fn is_safe(method: &http::Method) -> bool {
+    matches!(
+        *method,
+        http::Method::GET | http::Method::HEAD | http::Method::OPTIONS
+    )
+}
+
+fn sent(req: &http::Request<serde_json::Value>) -> Option<String> {
+    ft_sdk::from_request::fields::header(CSRF_HEADER, req).or_else(|| match req.body() {
+        serde_json::Value::Object(map) => map
+            .get(CSRF_FIELD)
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string()),
+        _ => None,
+    })
+}
+
+fn matches(expected: Option<&str>, sent: Option<&str>) -> bool {
+    match (expected, sent) {
+        (Some(e), Some(s)) if !e.is_empty() => ft_sdk::utils::constant_time_eq(e, s),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn matches() {
+        assert!(super::matches(Some("abc"), Some("abc")));
+        assert!(!super::matches(Some("abc"), Some("abd")));
+        assert!(!super::matches(Some("abc"), Some("ab")));
+        assert!(!super::matches(Some("abc"), None));
+        assert!(!super::matches(None, Some("")));
+        assert!(!super::matches(Some(""), Some("")));
+    }
+
+    #[test]
+    fn sent() {
+        let req = http::Request::builder()
+            .method("POST")
+            .body(serde_json::json!({"csrf-token": "t1"}))
+            .unwrap();
+        assert_eq!(super::sent(&req).as_deref(), Some("t1"));
+
+        let req = http::Request::builder()
+            .method("POST")
+            .header("x-csrf-token", "t2")
+            .body(serde_json::json!({"csrf-token": "t1"}))
+            .unwrap();
+        assert_eq!(super::sent(&req).as_deref(), Some("t2"));
+    }
+}
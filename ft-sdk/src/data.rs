@@ -113,18 +113,17 @@ pub fn binary<S: AsRef<str>>(content: bytes::Bytes, content_type: S) -> Result {
 /// a 200-OK response, with an HTML meta-refresh tag to redirect the browser.
 ///
 /// ```rust
-///  let cookie = cookie::Cookie::build((ft_sdk::auth::SESSION_KEY, "some-uniq-key"))
-///         .domain("127.0.0.1")
-///         .path("")
-///         .max_age(cookie::time::Duration::seconds(34560000))
-///         .same_site(cookie::SameSite::Strict)
-///         .build();
-/// ft_sdk::data::browser_redirect_with_cookie("/", http::HeaderValue::from_str(cookie.to_string().as_str()).unwrap());
+/// let cookie = ft_sdk::cookie::SetCookie::new(ft_sdk::auth::SESSION_KEY, "some-uniq-key")
+///     .with_domain("127.0.0.1");
+/// ft_sdk::data::browser_redirect_with_cookie("/", cookie).unwrap();
 /// ```
-pub fn browser_redirect_with_cookie<S: AsRef<str>>(url: S, c: http::HeaderValue) -> Result {
+pub fn browser_redirect_with_cookie<S: AsRef<str>, C: ft_sdk::cookie::IntoCookie>(
+    url: S,
+    c: C,
+) -> Result {
     Ok(ft_sdk::chr::CHR::new(Output::Redirect(
         url.as_ref().to_string(),
-        c,
+        c.into_cookie(),
     )))
 }
 
@@ -10,6 +10,41 @@ pub enum SpecialError {
     ServerError(String),
     #[error("unauthorised: {0}")]
     Unauthorised(String),
+    #[error("bad request: {0}")]
+    BadRequest(String),
+    /// The path exists, but not for the request method. Holds the methods that are allowed.
+    #[error("method not allowed, allowed: {0:?}")]
+    MethodNotAllowed(Vec<String>),
+    #[error("forbidden: {0}")]
+    Forbidden(String),
+    #[error("conflict: {0}")]
+    Conflict(String),
+    #[error("gone: {0}")]
+    Gone(String),
+    #[error("payload too large: {0}")]
+    PayloadTooLarge(String),
+    #[error("unprocessable entity: {0}")]
+    UnprocessableEntity(String),
+    /// The CSRF token is missing or does not match the session, see [ft_sdk::csrf].
+    #[error("csrf token missing or invalid")]
+    InvalidCsrfToken,
+    /// Redirect the browser to the url, sent as `303 See Other`.
+    #[error("redirect to {0}")]
+    Redirect(String),
+    /// The session lacks scopes `provider` has to grant, see [ft_sdk::RequireScopes]. Sent as
+    /// `403 Forbidden`, JSON responses carry `provider` and `scopes`, so the UI can send the user
+    /// to log in again with them.
+    #[error("missing scopes for {provider}: {scopes:?}")]
+    MissingScopes {
+        provider: String,
+        scopes: Vec<String>,
+    },
+    /// Sent with a `Retry-After` header if `retry_after` seconds is set.
+    #[error("too many requests: {msg}")]
+    TooManyRequests {
+        msg: String,
+        retry_after: Option<u64>,
+    },
 }
 
 /// Create a page not found response.
@@ -54,6 +89,113 @@ pub fn unauthorised_(msg: String) -> SpecialError {
     SpecialError::Unauthorised(msg)
 }
 
+/// Create a bad request response.
+#[macro_export]
+macro_rules! bad_request {
+    ($($t:tt)*) => {{
+        let msg = format!($($t)*);
+        $crate::bad_request_(msg)
+    }};
+}
+
+#[doc(hidden)]
+pub fn bad_request_(msg: String) -> SpecialError {
+    SpecialError::BadRequest(msg)
+}
+
+/// Create a forbidden response, for when the user is known but is not allowed to do something.
+#[macro_export]
+macro_rules! forbidden {
+    ($($t:tt)*) => {{
+        let msg = format!($($t)*);
+        $crate::forbidden_(msg)
+    }};
+}
+
+#[doc(hidden)]
+pub fn forbidden_(msg: String) -> SpecialError {
+    SpecialError::Forbidden(msg)
+}
+
+/// Create a conflict response, e.g. when creating something that already exists.
+#[macro_export]
+macro_rules! conflict {
+    ($($t:tt)*) => {{
+        let msg = format!($($t)*);
+        $crate::conflict_(msg)
+    }};
+}
+
+#[doc(hidden)]
+pub fn conflict_(msg: String) -> SpecialError {
+    SpecialError::Conflict(msg)
+}
+
+/// Create a gone response, for things that existed but have been removed.
+#[macro_export]
+macro_rules! gone {
+    ($($t:tt)*) => {{
+        let msg = format!($($t)*);
+        $crate::gone_(msg)
+    }};
+}
+
+#[doc(hidden)]
+pub fn gone_(msg: String) -> SpecialError {
+    SpecialError::Gone(msg)
+}
+
+/// Create a payload too large response.
+#[macro_export]
+macro_rules! payload_too_large {
+    ($($t:tt)*) => {{
+        let msg = format!($($t)*);
+        $crate::payload_too_large_(msg)
+    }};
+}
+
+#[doc(hidden)]
+pub fn payload_too_large_(msg: String) -> SpecialError {
+    SpecialError::PayloadTooLarge(msg)
+}
+
+/// Create an unprocessable entity response.
+#[macro_export]
+macro_rules! unprocessable_entity {
+    ($($t:tt)*) => {{
+        let msg = format!($($t)*);
+        $crate::unprocessable_entity_(msg)
+    }};
+}
+
+#[doc(hidden)]
+pub fn unprocessable_entity_(msg: String) -> SpecialError {
+    SpecialError::UnprocessableEntity(msg)
+}
+
+/// Create a too many requests response. Use `retry_after = <seconds>` as the first argument to
+/// send the `Retry-After` header:
+///
+/// ```rust,ignore
+/// return Err(ft_sdk::too_many_requests!(retry_after = 60, "slow down").into());
+/// ```
+#[macro_export]
+macro_rules! too_many_requests {
+    (retry_after = $s:expr, $($t:tt)*) => {{
+        let msg = format!($($t)*);
+        $crate::too_many_requests_(msg, Some($s))
+    }};
+    ($($t:tt)*) => {{
+        let msg = format!($($t)*);
+        $crate::too_many_requests_(msg, None)
+    }};
+}
+
+#[doc(hidden)]
+pub fn too_many_requests_(msg: String, retry_after: Option<u64>) -> SpecialError {
+    SpecialError::TooManyRequests { msg, retry_after }
+}
+
 pub fn single_error<K: AsRef<str>, E: AsRef<str>>(k: K, e: E) -> SpecialError {
     SpecialError::Single(k.as_ref().to_string(), e.as_ref().to_string())
 }
@@ -67,30 +209,195 @@ fn je(r: Result<http::Response<bytes::Bytes>, ft_sdk::Error>) -> http::Response<
     })
 }
 
+/// In production mode, internal error details are logged but not sent to the client. It is
+/// enabled by setting the `PRODUCTION` environment variable to anything but `""`, `0` or `false`.
+pub fn is_production() -> bool {
+    #[cfg(target_family = "wasm")]
+    let v = ft_sys::env::var("PRODUCTION".to_string());
+    #[cfg(not(target_family = "wasm"))]
+    let v = std::env::var("PRODUCTION").ok();
+
+    !matches!(v.as_deref(), None | Some("" | "0" | "false"))
+}
+
+/// Convert an error to a plain text response.
 pub fn handle_error(e: anyhow::Error) -> http::Response<bytes::Bytes> {
-    if let Some(field_error) = e.downcast_ref::<SpecialError>() {
-        ft_sdk::println!("special error: {field_error}");
-        return match field_error {
-            SpecialError::Single(k, se) => je(crate::json(serde_json::json!({"errors": {k: se}}))),
-            SpecialError::Multi(me) => je(crate::json(serde_json::json!({"errors": me}))),
-            SpecialError::NotFound(msg) => http::Response::builder()
-                .status(http::StatusCode::NOT_FOUND)
-                .body(format!("page not found: {msg}\n").into())
-                .unwrap(),
-            SpecialError::Unauthorised(msg) => http::Response::builder()
-                .status(http::StatusCode::UNAUTHORIZED)
-                .body(format!("unauthorised: {msg}\n").into())
-                .unwrap(),
-            SpecialError::ServerError(msg) => http::Response::builder()
-                .status(http::StatusCode::INTERNAL_SERVER_ERROR)
-                .body(format!("server error: {msg}\n").into())
-                .unwrap(),
-        };
+    render(e, false, is_production())
+}
+
+/// Convert an error to a response for `req`. If the client prefers JSON over HTML, errors are
+/// sent as `application/problem+json` (RFC 7807).
+pub fn handle_error_for(
+    req: &http::Request<serde_json::Value>,
+    e: anyhow::Error,
+) -> http::Response<bytes::Bytes> {
+    render(e, wants_json(req), is_production())
+}
+
+pub(crate) fn wants_json(req: &http::Request<serde_json::Value>) -> bool {
+    let accept = match <ft_sdk::Accept as ft_sdk::FromRequest>::from_request(req) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+    // text/html is listed first, so `*/*` alone does not pick json
+    matches!(
+        accept.best(&["text/html", "application/json", "application/problem+json"]),
+        Some("application/json" | "application/problem+json")
+    )
+}
+
+fn render(e: anyhow::Error, json: bool, production: bool) -> http::Response<bytes::Bytes> {
+    ft_sdk::println!("Error: {e:?}");
+
+    let special = match e.downcast_ref::<SpecialError>() {
+        Some(v) => v,
+        None => {
+            let detail = if production {
+                None
+            } else {
+                Some(format!("json error: {e:?}"))
+            };
+            return respond(
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                detail,
+                vec![],
+                serde_json::Map::new(),
+                json,
+            );
+        }
+    };
+
+    ft_sdk::println!("special error: {special}");
+    let mut members = serde_json::Map::new();
+    let (status, msg, headers) = match special {
+        SpecialError::Single(k, se) => {
+            return je(crate::json(serde_json::json!({"errors": {k: se}})));
+        }
+        SpecialError::Multi(me) => return je(crate::json(serde_json::json!({"errors": me}))),
+        SpecialError::NotFound(msg) => (
+            http::StatusCode::NOT_FOUND,
+            format!("page not found: {msg}"),
+            vec![],
+        ),
+        SpecialError::Unauthorised(msg) => (
+            http::StatusCode::UNAUTHORIZED,
+            format!("unauthorised: {msg}"),
+            vec![],
+        ),
+        SpecialError::ServerError(_) if production => {
+            return respond(
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                None,
+                vec![],
+                members,
+                json,
+            );
+        }
+        SpecialError::ServerError(msg) => (
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("server error: {msg}"),
+            vec![],
+        ),
+        SpecialError::BadRequest(msg) => (
+            http::StatusCode::BAD_REQUEST,
+            format!("bad request: {msg}"),
+            vec![],
+        ),
+        SpecialError::MethodNotAllowed(allowed) => (
+            http::StatusCode::METHOD_NOT_ALLOWED,
+            format!("method not allowed, allowed: {}", allowed.join(", ")),
+            vec![(http::header::ALLOW, allowed.join(", "))],
+        ),
+        SpecialError::Forbidden(msg) => (
+            http::StatusCode::FORBIDDEN,
+            format!("forbidden: {msg}"),
+            vec![],
+        ),
+        SpecialError::Conflict(msg) => (
+            http::StatusCode::CONFLICT,
+            format!("conflict: {msg}"),
+            vec![],
+        ),
+        SpecialError::InvalidCsrfToken => (
+            http::StatusCode::FORBIDDEN,
+            "forbidden: csrf token missing or invalid".to_string(),
+            vec![],
+        ),
+        SpecialError::MissingScopes { provider, scopes } => {
+            members.insert("provider".to_string(), provider.as_str().into());
+            members.insert("scopes".to_string(), scopes.clone().into());
+            (
+                http::StatusCode::FORBIDDEN,
+                format!(
+                    "forbidden: log in again with {provider} to grant: {}",
+                    scopes.join(", ")
+                ),
+                vec![],
+            )
+        }
+        SpecialError::Redirect(url) => (
+            http::StatusCode::SEE_OTHER,
+            format!("see other: {url}"),
+            vec![(http::header::LOCATION, url.to_string())],
+        ),
+        SpecialError::Gone(msg) => (http::StatusCode::GONE, format!("gone: {msg}"), vec![]),
+        SpecialError::PayloadTooLarge(msg) => (
+            http::StatusCode::PAYLOAD_TOO_LARGE,
+            format!("payload too large: {msg}"),
+            vec![],
+        ),
+        SpecialError::UnprocessableEntity(msg) => (
+            http::StatusCode::UNPROCESSABLE_ENTITY,
+            format!("unprocessable entity: {msg}"),
+            vec![],
+        ),
+        SpecialError::TooManyRequests { msg, retry_after } => (
+            http::StatusCode::TOO_MANY_REQUESTS,
+            format!("too many requests: {msg}"),
+            retry_after
+                .map(|s| vec![(http::header::RETRY_AFTER, s.to_string())])
+                .unwrap_or_default(),
+        ),
+    };
+
+    respond(status, Some(msg), headers, members, json)
+}
+
+/// Build the error response, as `application/problem+json` if `json` is set, else as plain text.
+/// Without `detail` the body only has the status. `members` are added to the problem object as
+/// extension members, they are not part of the plain text body.
+fn respond(
+    status: http::StatusCode,
+    detail: Option<String>,
+    headers: Vec<(http::HeaderName, String)>,
+    members: serde_json::Map<String, serde_json::Value>,
+    json: bool,
+) -> http::Response<bytes::Bytes> {
+    let mut builder = http::Response::builder().status(status);
+    for (k, v) in headers {
+        builder = builder.header(k, v);
     }
-    http::Response::builder()
-        .status(http::StatusCode::INTERNAL_SERVER_ERROR)
-        .body(format!("json error: {e:?}\n").into())
-        .unwrap()
+
+    let title = status.canonical_reason().unwrap_or_default();
+    let body = if json {
+        builder = builder.header(http::header::CONTENT_TYPE, "application/problem+json");
+        let mut problem = serde_json::json!({
+            "type": "about:blank",
+            "title": title,
+            "status": status.as_u16(),
+        });
+        if let Some(detail) = detail {
+            problem["detail"] = detail.into();
+        }
+        for (k, v) in members {
+            problem[k] = v;
+        }
+        problem.to_string()
+    } else {
+        format!("{}\n", detail.unwrap_or_else(|| title.to_ascii_lowercase()))
+    };
+
+    builder.body(body.into()).unwrap()
 }
 
 #[cfg(test)]
@@ -215,4 +522,67 @@ mod test {
         // This example works, but end-user has to make sure std::error::Error traits
         // source() works correctly (here we have used `[from]` to ensure that)
     }
+
+    fn body(r: &http::Response<bytes::Bytes>) -> String {
+        String::from_utf8(r.body().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn problem_json() {
+        let e = crate::too_many_requests!(retry_after = 30, "slow down");
+        let r = super::render(e.into(), true, false);
+        assert_eq!(r.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(r.headers()[http::header::RETRY_AFTER], "30");
+        assert_eq!(
+            r.headers()[http::header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body(&r)).unwrap(),
+            serde_json::json!({
+                "type": "about:blank",
+                "title": "Too Many Requests",
+                "status": 429,
+                "detail": "too many requests: slow down",
+            })
+        );
+
+        let r = super::render(crate::forbidden!("no").into(), false, false);
+        assert_eq!(r.status(), http::StatusCode::FORBIDDEN);
+        assert_eq!(body(&r), "forbidden: no\n");
+    }
+
+    #[test]
+    fn missing_scopes() {
+        let e = super::SpecialError::MissingScopes {
+            provider: "github".to_string(),
+            scopes: vec!["repo".to_string(), "read:org".to_string()],
+        };
+        let r = super::render(e.into(), true, false);
+        assert_eq!(r.status(), http::StatusCode::FORBIDDEN);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body(&r)).unwrap(),
+            serde_json::json!({
+                "type": "about:blank",
+                "title": "Forbidden",
+                "status": 403,
+                "detail": "forbidden: log in again with github to grant: repo, read:org",
+                "provider": "github",
+                "scopes": ["repo", "read:org"],
+            })
+        );
+    }
+
+    #[test]
+    fn production_hides_internal_errors() {
+        let r = super::render(anyhow::anyhow!("db password is hunter2"), false, true);
+        assert_eq!(r.status(), http::StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body(&r), "internal server error\n");
+
+        let r = super::render(crate::server_error!("secret").into(), false, true);
+        assert_eq!(body(&r), "internal server error\n");
+
+        let r = super::render(anyhow::anyhow!("oops"), false, false);
+        assert!(body(&r).contains("oops"));
+    }
 }
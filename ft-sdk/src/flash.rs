@@ -0,0 +1,120 @@
+//! One shot messages for the next page, e.g. "Saved!" after a form is submitted and the browser
+//! is redirected.
+//!
+//! Messages are kept in the session data under [FLASH_KEY], until the [Flashes] extractor reads
+//! them, which also clears them:
+//!
+//! ```rust,ignore
+//! #[ft_sdk::form]
+//! fn save(session: ft_sdk::Session, mut conn: ft_sdk::Connection) -> ft_sdk::form::Result {
+//!     // ..
+//!     ft_sdk::Flash::push(&mut conn, &session, ft_sdk::flash::Level::Success, "Saved!")?;
+//!     ft_sdk::form::redirect("/")
+//! }
+//!
+//! #[ft_sdk::data]
+//! fn flashes(flashes: ft_sdk::Flashes) -> ft_sdk::data::Result {
+//!     ft_sdk::data::json(flashes)
+//! }
+//! ```
+//!
+//! The JSON is a list of `{"level": "success", "message": "Saved!"}`, which ftd pages can read
+//! into a list of:
+//!
+//! ```ftd
+//! -- record flash:
+//! string level:
+//! string message:
+//! ```
+
+/// Key in the session data the pending messages are stored under.
+pub const FLASH_KEY: &str = "fastn-flash";
+
+/// How a message should be shown. Serialized in lowercase, `"success"`, for use in ftd pages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Flash {
+    pub level: Level,
+    pub message: String,
+}
+
+impl Flash {
+    /// Add a message for the next request of the session that reads [Flashes]. The
+    /// [ft_sdk::Session] extractor gives the session, creating one if there is none.
+    pub fn push<S: AsRef<str>>(
+        conn: &mut ft_sdk::Connection,
+        session_id: &ft_sdk::SessionID,
+        level: Level,
+        message: S,
+    ) -> Result<(), ft_sdk::session::SetKeyError> {
+        let flash = Flash {
+            level,
+            message: message.as_ref().to_string(),
+        };
+        session_id.update_key(conn, FLASH_KEY, |pending: Option<Vec<Flash>>| {
+            let mut pending = pending.unwrap_or_default();
+            pending.push(flash);
+            Some(pending)
+        })?;
+        Ok(())
+    }
+}
+
+/// The messages added by [Flash::push] since they were last read. Reading them clears them.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(transparent)]
+pub struct Flashes(pub Vec<Flash>);
+
+impl ft_sdk::FromRequest for Flashes {
+    fn from_request(_req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        ft_sdk::from_request::connection::with(|conn| {
+            let sid = match ft_sdk::from_request::session::current(conn)? {
+                Some(v) => v,
+                None => return Ok(Flashes::default()),
+            };
+            let pending: Option<Vec<Flash>> = sid.data(conn)?.get_key(FLASH_KEY);
+            if pending.is_none_or(|p| p.is_empty()) {
+                return Ok(Flashes::default());
+            }
+
+            // taken in a locked update, so a message pushed meanwhile is not lost
+            let mut pending = vec![];
+            sid.update_key(conn, FLASH_KEY, |p: Option<Vec<Flash>>| {
+                pending = p.unwrap_or_default();
+                None
+            })?;
+            Ok(Flashes(pending))
+        })
+    }
+}
+
+impl std::ops::Deref for Flashes {
+    type Target = Vec<Flash>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn json() {
+        let flashes = super::Flashes(vec![super::Flash {
+            level: super::Level::Success,
+            message: "Saved!".to_string(),
+        }]);
+        assert_eq!(
+            serde_json::to_value(&flashes).unwrap(),
+            serde_json::json!([{"level": "success", "message": "Saved!"}])
+        );
+    }
+}
@@ -0,0 +1,179 @@
+/// The request body exactly as it was sent, e.g. to verify the signature of a webhook.
+///
+/// This is available for every request, whatever the `Content-Type`, in addition to the parsed
+/// body handlers get through extractors like [ft_sdk::Json] or [ft_sdk::Form].
+#[derive(Clone, Debug, Default)]
+pub struct RawBody(pub bytes::Bytes);
+
+impl ft_sdk::FromRequest for RawBody {
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        match req.extensions().get::<RawBody>() {
+            Some(v) => Ok(v.clone()),
+            None => Err(ft_sdk::server_error!("raw body not found in request extensions").into()),
+        }
+    }
+}
+
+impl AsRef<[u8]> for RawBody {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// The request body as a string. Returns an error if the body is not valid UTF-8.
+pub struct Text(pub String);
+
+impl ft_sdk::FromRequest for Text {
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        let RawBody(b) = RawBody::from_request(req)?;
+        match String::from_utf8(b.to_vec()) {
+            Ok(s) => Ok(Text(s)),
+            Err(_) => Err(ft_sdk::single_error("body", "body is not valid utf-8").into()),
+        }
+    }
+}
+
+impl AsRef<str> for Text {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Parse the body based on the `Content-Type` header:
+///
+/// - `application/json`, `*+json`, or no `Content-Type`: parsed as JSON.
+/// - `application/x-www-form-urlencoded`: an object with string values, keys repeated in the
+///   body become arrays of strings.
+/// - `multipart/form-data`: same as urlencoded for the non-file fields, files are stored in the
+///   request extensions, see [ft_sdk::from_request::multipart].
+/// - `text/*`: a string, invalid UTF-8 is replaced.
+/// - anything else: `Null`, use [RawBody] to read such bodies.
+///
+/// An empty body is always `Null`.
+pub(crate) fn parse(
+    head: &mut http::request::Parts,
+    body: &bytes::Bytes,
+) -> Result<serde_json::Value, ft_sdk::Error> {
+    if body.is_empty() {
+        return Ok(serde_json::Value::Null);
+    }
+
+    let content_type = match head
+        .headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+    {
+        Some(v) => v,
+        None => return json(body),
+    };
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    if mime == "application/json" || mime.ends_with("+json") {
+        return json(body);
+    }
+
+    if mime == "application/x-www-form-urlencoded" {
+        let pairs: Vec<(String, String)> =
+            serde_urlencoded::from_bytes(body).map_err(|e| ft_sdk::bad_request!("{e}"))?;
+        let mut map = serde_json::Map::new();
+        for (k, v) in pairs {
+            insert(&mut map, k, v);
+        }
+        return Ok(serde_json::Value::Object(map));
+    }
+
+    if mime == "multipart/form-data" {
+        // a body the client got wrong, not a server error
+        let (fields, files) = ft_sdk::from_request::multipart::boundary(content_type)
+            .and_then(|b| ft_sdk::from_request::multipart::parse(&b, body))
+            .map_err(|e| ft_sdk::bad_request!("{e}"))?;
+        head.extensions.insert(files);
+        return Ok(fields);
+    }
+
+    if mime.starts_with("text/") {
+        return Ok(serde_json::Value::String(
+            String::from_utf8_lossy(body).into_owned(),
+        ));
+    }
+
+    Ok(serde_json::Value::Null)
+}
+
+/// A body that is not valid JSON is the client's mistake, not a server error.
+fn json(body: &bytes::Bytes) -> Result<serde_json::Value, ft_sdk::Error> {
+    Ok(serde_json::from_slice(body).map_err(|e| ft_sdk::bad_request!("invalid json body: {e}"))?)
+}
+
+/// Insert a form field, turning the value into an array if `k` is repeated.
+pub(crate) fn insert(map: &mut serde_json::Map<String, serde_json::Value>, k: String, v: String) {
+    match map.get_mut(&k) {
+        Some(serde_json::Value::Array(a)) => a.push(v.into()),
+        Some(existing) => *existing = serde_json::json!([existing.take(), v]),
+        None => {
+            map.insert(k, v.into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    fn parse(content_type: Option<&str>, body: &str) -> serde_json::Value {
+        let mut req = http::Request::builder();
+        if let Some(ct) = content_type {
+            req = req.header(http::header::CONTENT_TYPE, ct);
+        }
+        let (mut head, _) = req.body(()).unwrap().into_parts();
+        super::parse(&mut head, &bytes::Bytes::copy_from_slice(body.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn content_types() {
+        assert_eq!(parse(None, r#"{"a": 1}"#), serde_json::json!({"a": 1}));
+        assert_eq!(
+            parse(Some("application/json; charset=utf-8"), "[1]"),
+            serde_json::json!([1])
+        );
+        assert_eq!(
+            parse(
+                Some("application/x-www-form-urlencoded"),
+                "name=a+b&tag=x&tag=y&tag=z"
+            ),
+            serde_json::json!({"name": "a b", "tag": ["x", "y", "z"]})
+        );
+        assert_eq!(parse(Some("text/plain"), "hello"), "hello");
+        assert_eq!(
+            parse(Some("application/octet-stream"), "\u{1}"),
+            serde_json::Value::Null
+        );
+        assert_eq!(parse(Some("text/plain"), ""), serde_json::Value::Null);
+    }
+
+    #[test]
+    fn malformed() {
+        for ct in [
+            "application/json",
+            "multipart/form-data",
+            "multipart/form-data; boundary=x",
+        ] {
+            let (mut head, _) = http::Request::builder()
+                .header(http::header::CONTENT_TYPE, ct)
+                .body(())
+                .unwrap()
+                .into_parts();
+            let e = super::parse(&mut head, &bytes::Bytes::from_static(b"garbage")).unwrap_err();
+            assert!(
+                matches!(
+                    e.downcast_ref::<ft_sdk::SpecialError>(),
+                    Some(ft_sdk::SpecialError::BadRequest(_))
+                ),
+                "{e:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,31 @@
+//! The database connection shared by the extractors of the current request, so a handler that
+//! takes, say, [ft_sdk::RequireUser] and [ft_sdk::Connection] opens one connection, not two.
+
+thread_local! {
+    static CONNECTION: std::cell::RefCell<Option<ft_sdk::Connection>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Drop the connection of the previous request, called when the request is read.
+pub(crate) fn reset() {
+    CONNECTION.with(|c| *c.borrow_mut() = None);
+}
+
+/// Run `f` with the shared connection, opening it if this is the first use in this request.
+pub(crate) fn with<T>(
+    f: impl FnOnce(&mut ft_sdk::Connection) -> Result<T, ft_sdk::Error>,
+) -> Result<T, ft_sdk::Error> {
+    let mut conn = take()?;
+    let r = f(&mut conn);
+    CONNECTION.with(|c| *c.borrow_mut() = Some(conn));
+    r
+}
+
+/// Take the shared connection, used by the [ft_sdk::Connection] extractor. Extractors that run
+/// after this open a new one.
+pub(crate) fn take() -> Result<ft_sdk::Connection, ft_sdk::Error> {
+    match CONNECTION.with(|c| c.borrow_mut().take()) {
+        Some(c) => Ok(c),
+        None => Ok(ft_sdk::default_connection()?),
+    }
+}
@@ -12,21 +12,52 @@ impl<const KEY: &'static str> std::fmt::Display for Cookie<KEY> {
 
 impl<const KEY: &'static str> ft_sdk::FromRequest for Cookie<KEY> {
     fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
-        Ok(Cookie(
-            req.headers()
-                .get("cookie")
-                .and_then(|v| v.to_str().ok())
-                .and_then(|v| {
-                    v.split(';')
-                        .find(|v| v.trim_start().starts_with(&format!("{KEY}=")))
-                        .map(|v| {
-                            v.trim_start()
-                                .strip_prefix(&format!("{KEY}="))
-                                .unwrap()
-                                .trim_start()
-                                .to_string()
-                        })
-                }),
-        ))
+        Ok(Cookie(ft_sdk::from_request::fields::cookie(KEY, req)))
+    }
+}
+
+/// The value of a cookie set using [ft_sdk::chr::CHR::with_signed_cookie]. `None` if the cookie
+/// is missing, or has been tampered with.
+#[derive(Clone, Debug)]
+pub struct SignedCookie<const KEY: &'static str, T: serde::de::DeserializeOwned = String>(
+    pub Option<T>,
+);
+
+impl<const KEY: &'static str, T: serde::de::DeserializeOwned> ft_sdk::FromRequest
+    for SignedCookie<KEY, T>
+{
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        let v = match ft_sdk::from_request::fields::cookie(KEY, req) {
+            Some(v) => v,
+            None => return Ok(SignedCookie(None)),
+        };
+        let v = ft_sdk::cookie::verify(KEY, &v, ft_sys::decrypt);
+        if v.is_none() {
+            ft_sdk::println!("signed cookie {KEY} is invalid, ignoring it");
+        }
+        Ok(SignedCookie(v))
+    }
+}
+
+/// The value of a cookie set using [ft_sdk::chr::CHR::with_encrypted_cookie]. `None` if the
+/// cookie is missing, or can not be decrypted.
+#[derive(Clone, Debug)]
+pub struct EncryptedCookie<const KEY: &'static str, T: serde::de::DeserializeOwned = String>(
+    pub Option<T>,
+);
+
+impl<const KEY: &'static str, T: serde::de::DeserializeOwned> ft_sdk::FromRequest
+    for EncryptedCookie<KEY, T>
+{
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        let v = match ft_sdk::from_request::fields::cookie(KEY, req) {
+            Some(v) => v,
+            None => return Ok(EncryptedCookie(None)),
+        };
+        let v = ft_sdk::cookie::decrypt(KEY, &v, ft_sys::decrypt);
+        if v.is_none() {
+            ft_sdk::println!("encrypted cookie {KEY} can not be decrypted, ignoring it");
+        }
+        Ok(EncryptedCookie(v))
     }
 }
@@ -29,16 +29,6 @@ impl<const KEY: &'static str, T: serde::de::DeserializeOwned + std::default::Def
     ft_sdk::FromRequest for Default<KEY, T>
 {
     fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
-        match req.body() {
-            serde_json::Value::Null => Ok(Default(std::default::Default::default())),
-            serde_json::Value::Object(map) => {
-                if let Some(value) = map.get(KEY) {
-                    Ok(serde_json::from_value(value.clone()).map(Default)?)
-                } else {
-                    Ok(Default(std::default::Default::default()))
-                }
-            }
-            _ => Err(ft_sdk::single_error(KEY, "body is not json object").into()),
-        }
+        ft_sdk::from_request::fields::body_default(KEY, req).map(Default)
     }
 }
@@ -0,0 +1,61 @@
+/// Accumulates field errors reported by extractors, so all the fields of a form can be
+/// validated in one go instead of the user fixing them one round-trip at a time.
+///
+/// [ft_sdk::SpecialError::Single] and [ft_sdk::SpecialError::Multi] are merged into a single
+/// [ft_sdk::SpecialError::Multi]. Any other error is not a field error, and is returned right
+/// away by [FieldErrors::check].
+///
+/// ```rust,ignore
+/// let mut errors = ft_sdk::from_request::FieldErrors::default();
+/// let name = errors.check(ft_sdk::from_request::fields::body::<String>("name", req))?;
+/// let age = errors.check(ft_sdk::from_request::fields::body::<i32>("age", req))?;
+/// errors.finish()?;
+/// // name and age are guaranteed to be `Some` now
+/// ```
+#[derive(Default, Debug)]
+pub struct FieldErrors(ft_sdk::FormError);
+
+impl FieldErrors {
+    /// Returns `Ok(None)` if `r` is a field error, which is recorded. The value is guaranteed to
+    /// be `Some` once [FieldErrors::finish] has returned `Ok`.
+    pub fn check<T>(&mut self, r: Result<T, ft_sdk::Error>) -> Result<Option<T>, ft_sdk::Error> {
+        let e = match r {
+            Ok(t) => return Ok(Some(t)),
+            Err(e) => e,
+        };
+
+        match e.downcast::<ft_sdk::SpecialError>() {
+            Ok(ft_sdk::SpecialError::Single(k, v)) => {
+                self.0.insert(k, v);
+                Ok(None)
+            }
+            Ok(ft_sdk::SpecialError::Multi(m)) => {
+                self.0.extend(m);
+                Ok(None)
+            }
+            Ok(e) => Err(e.into()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// [FieldErrors::check] the result of extracting `T` from the request.
+    pub fn extract<T: ft_sdk::FromRequest>(
+        &mut self,
+        req: &http::Request<serde_json::Value>,
+    ) -> Result<Option<T>, ft_sdk::Error> {
+        self.check(T::from_request(req))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Return all the recorded field errors as a single [ft_sdk::SpecialError::Multi].
+    pub fn finish(self) -> Result<(), ft_sdk::Error> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        Err(ft_sdk::SpecialError::Multi(self.0).into())
+    }
+}
@@ -0,0 +1,299 @@
+//! Read a single named value from the request.
+//!
+//! These are the functions behind the field extractors, like [ft_sdk::Required] and
+//! [ft_sdk::Cookie], taking the key at runtime instead of as a const generic. They are used by
+//! `#[derive(ft_sdk::FromRequest)]`, and can be used directly in a custom
+//! [ft_sdk::FromRequest] implementation.
+//!
+//! Errors about the value itself, e.g. missing or invalid, are returned as
+//! [ft_sdk::SpecialError::Single] for `key`.
+
+/// Read `key` from the request body, which must be a JSON object. Empty strings are treated as
+/// missing. This is what [ft_sdk::Required] does.
+// We need `'static` here so that we can use the `std::any::TypeId::of()`. I read this thread
+// to understand what it means: https://www.reddit.com/r/learnrust/comments/12fpu7m/what_does_static_mean_in_a_trait/
+// types like i32, Vec<i32>, String as `'static`, but not say `&'a str`. We are using
+// serde::de::DeserializeOwned as a trait, which feels is also "owned" and hence `'static`, so
+// adding 'static here does not limit the types that can be used with this trait (beyond what
+// serde::de::DeserializeOwned already limits).
+pub fn body<T: serde::de::DeserializeOwned + 'static>(
+    key: &str,
+    req: &http::Request<serde_json::Value>,
+) -> Result<T, ft_sdk::Error> {
+    match req.body() {
+        serde_json::Value::Null => {
+            Err(ft_sdk::single_error(key, "body is Null, expected Object").into())
+        }
+        serde_json::Value::Object(map) => {
+            if let Some(value) = map.get(key) {
+                if std::any::TypeId::of::<T>() == std::any::TypeId::of::<String>() {
+                    if let serde_json::Value::String(s) = value {
+                        if s.is_empty() {
+                            return Err(ft_sdk::single_error(key, "field is empty").into());
+                        }
+                    }
+                }
+                Ok(serde_json::from_value(value.clone())?)
+            } else {
+                Err(ft_sdk::single_error(key, "missing field").into())
+            }
+        }
+        _ => Err(ft_sdk::single_error(key, "body is not json object").into()),
+    }
+}
+
+/// Read `key` from the request body, `None` if it is missing. This is what [ft_sdk::Optional]
+/// does.
+pub fn body_optional<T: serde::de::DeserializeOwned>(
+    key: &str,
+    req: &http::Request<serde_json::Value>,
+) -> Result<Option<T>, ft_sdk::Error> {
+    match req.body() {
+        serde_json::Value::Null => {
+            Err(ft_sdk::single_error(key, "body is Null, expected Object").into())
+        }
+        serde_json::Value::Object(map) => {
+            if let Some(value) = map.get(key) {
+                Ok(serde_json::from_value(value.clone()).map(Some)?)
+            } else {
+                Ok(None)
+            }
+        }
+        _ => Err(ft_sdk::single_error(key, "body is not json object").into()),
+    }
+}
+
+/// Read `key` from the request body, `T::default()` if it is missing, or if there is no body.
+/// This is what [ft_sdk::Default] does.
+pub fn body_default<T: serde::de::DeserializeOwned + std::default::Default>(
+    key: &str,
+    req: &http::Request<serde_json::Value>,
+) -> Result<T, ft_sdk::Error> {
+    match req.body() {
+        serde_json::Value::Null => Ok(std::default::Default::default()),
+        serde_json::Value::Object(map) => {
+            if let Some(value) = map.get(key) {
+                Ok(serde_json::from_value(value.clone())?)
+            } else {
+                Ok(std::default::Default::default())
+            }
+        }
+        _ => Err(ft_sdk::single_error(key, "body is not json object").into()),
+    }
+}
+
+/// Read `key` from the query string, or if not present there, from the request body. Empty
+/// values in the body are an error. This is what [ft_sdk::Query] does.
+pub fn query(key: &str, req: &http::Request<serde_json::Value>) -> Result<String, ft_sdk::Error> {
+    let query = req.uri().query().unwrap_or_default();
+    let args: Vec<(String, String)> = match serde_urlencoded::from_str(query) {
+        Ok(v) => v,
+        Err(_) => {
+            ft_sdk::println!("failed to parse query: {}", query);
+            vec![]
+        }
+    };
+
+    if let Some((_, v)) = args.into_iter().find(|(k, _)| k == key) {
+        return Ok(v);
+    }
+
+    if let serde_json::Value::Object(map) = req.body() {
+        if let Some(serde_json::Value::String(s)) = map.get(key) {
+            if s.is_empty() {
+                return Err(ft_sdk::single_error(key, "field is empty").into());
+            }
+
+            return Ok(s.to_string());
+        }
+    }
+
+    Err(ft_sdk::single_error(key, format!("{key} is missing in input")).into())
+}
+
+/// Same as [query], but returns `None` if `key` is missing or empty.
+pub fn query_optional(key: &str, req: &http::Request<serde_json::Value>) -> Option<String> {
+    let query = req.uri().query().unwrap_or_default();
+    let args: Vec<(String, String)> = serde_urlencoded::from_str(query).ok()?;
+
+    if let Some((_, v)) = args.into_iter().find(|(k, _)| k == key) {
+        return Some(v);
+    }
+
+    if let serde_json::Value::Object(map) = req.body() {
+        if let Some(serde_json::Value::String(s)) = map.get(key) {
+            if s.is_empty() {
+                return None;
+            }
+
+            return Some(s.to_string());
+        }
+    }
+
+    None
+}
+
+/// Read the cookie named `key`, percent-decoding the value if it is valid percent-encoded UTF-8.
+/// This is what [ft_sdk::Cookie] does.
+pub fn cookie(key: &str, req: &http::Request<serde_json::Value>) -> Option<String> {
+    raw_cookie(key, req).map(|v| ft_sdk::utils::percent_decode(&v).unwrap_or(v))
+}
+
+fn raw_cookie(key: &str, req: &http::Request<serde_json::Value>) -> Option<String> {
+    req.headers()
+        .get("cookie")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| {
+            v.split(';')
+                .find(|v| v.trim_start().starts_with(&format!("{key}=")))
+                .map(|v| {
+                    v.trim_start()
+                        .strip_prefix(&format!("{key}="))
+                        .unwrap()
+                        .trim_start()
+                        .to_string()
+                })
+        })
+}
+
+/// Read the header named `name`. Headers that are not valid strings are treated as missing.
+pub fn header(name: &str, req: &http::Request<serde_json::Value>) -> Option<String> {
+    req.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+/// Read the `{key}` segment of the route matched by [ft_sdk::Router].
+pub fn path(key: &str, req: &http::Request<serde_json::Value>) -> Option<String> {
+    req.extensions()
+        .get::<ft_sdk::router::PathParamsMap>()
+        .and_then(|p| p.0.iter().find(|(k, _)| k == key))
+        .map(|(_, v)| v.to_string())
+}
+
+/// Parse a string value, e.g. one returned by [cookie], into `T`. Values are parsed the same way
+/// as query string values, so numbers, booleans and strings all work.
+pub fn parse<T: serde::de::DeserializeOwned>(key: &str, value: &str) -> Result<T, ft_sdk::Error> {
+    parse_str(value).map_err(|e| ft_sdk::single_error(key, format!("invalid value: {e}")).into())
+}
+
+pub(crate) fn parse_str<T: serde::de::DeserializeOwned>(
+    value: &str,
+) -> Result<T, serde_urlencoded::de::Error> {
+    let encoded = serde_urlencoded::to_string([("v", value)]).expect("strings can be encoded");
+    serde_urlencoded::from_str::<Vec<(String, T)>>(&encoded)
+        .map(|mut v| v.pop().expect("encoded has exactly one value").1)
+}
+
+/// [parse] a value that must be present.
+pub fn required<T: serde::de::DeserializeOwned>(
+    key: &str,
+    value: Option<String>,
+) -> Result<T, ft_sdk::Error> {
+    match value {
+        Some(v) => parse(key, &v),
+        None => Err(ft_sdk::single_error(key, "missing field").into()),
+    }
+}
+
+/// [parse] a value that may be missing.
+pub fn optional<T: serde::de::DeserializeOwned>(
+    key: &str,
+    value: Option<String>,
+) -> Result<Option<T>, ft_sdk::Error> {
+    value.map(|v| parse(key, &v)).transpose()
+}
+
+/// [parse] a value, using `T::default()` if it is missing.
+pub fn default<T: serde::de::DeserializeOwned + std::default::Default>(
+    key: &str,
+    value: Option<String>,
+) -> Result<T, ft_sdk::Error> {
+    Ok(optional(key, value)?.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn parse() {
+        assert_eq!(super::parse::<i32>("k", "42").unwrap(), 42);
+        assert!(super::parse::<bool>("k", "true").unwrap());
+        assert_eq!(super::parse::<String>("k", "a b&c").unwrap(), "a b&c");
+        assert_eq!(
+            super::parse::<i32>("k", "x")
+                .unwrap_err()
+                .downcast_ref::<ft_sdk::SpecialError>()
+                .map(|e| matches!(e, ft_sdk::SpecialError::Single(k, _) if k == "k")),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn cookie() {
+        let req = http::Request::builder()
+            .header("cookie", "a=1; b=x%3By%20z; c=100%")
+            .body(serde_json::Value::Null)
+            .unwrap();
+        assert_eq!(super::cookie("b", &req).as_deref(), Some("x;y z"));
+        // not valid percent-encoding, returned as is
+        assert_eq!(super::cookie("c", &req).as_deref(), Some("100%"));
+        assert_eq!(super::cookie("d", &req), None);
+    }
+
+    #[derive(ft_sdk::FromRequest, Debug)]
+    struct Input {
+        name: String,
+        #[query(rename = "p")]
+        page: u32,
+        #[header(default)]
+        x_count: u32,
+        #[cookie(optional)]
+        sid: Option<String>,
+    }
+
+    fn input(uri: &str, body: serde_json::Value) -> ft_sdk::Result<Input> {
+        let req = http::Request::builder()
+            .uri(uri)
+            .header("x-count", "3")
+            .body(body)
+            .unwrap();
+        ft_sdk::FromRequest::from_request(&req)
+    }
+
+    #[test]
+    fn derive() {
+        let i = input("/?p=2", serde_json::json!({"name": "a"})).unwrap();
+        assert_eq!(
+            (i.name.as_str(), i.page, i.x_count, i.sid),
+            ("a", 2, 3, None)
+        );
+
+        let e = input("/?p=x", serde_json::json!({})).unwrap_err();
+        match e.downcast_ref::<ft_sdk::SpecialError>() {
+            Some(ft_sdk::SpecialError::Multi(m)) => {
+                assert!(m.contains_key("name") && m.contains_key("p"), "{m:?}")
+            }
+            e => panic!("unexpected error: {e:?}"),
+        }
+    }
+
+    // fields named like the locals of the generated code
+    #[derive(ft_sdk::FromRequest, Debug)]
+    struct Shadowing {
+        errors: String,
+        #[query]
+        req: String,
+        after: u32,
+    }
+
+    #[test]
+    fn derive_shadowing() {
+        let req = http::Request::builder()
+            .uri("/?req=r")
+            .body(serde_json::json!({"errors": "e", "after": 1}))
+            .unwrap();
+        let s: Shadowing = ft_sdk::FromRequest::from_request(&req).unwrap();
+        assert_eq!((s.errors.as_str(), s.req.as_str(), s.after), ("e", "r", 1));
+    }
+}
@@ -3,30 +3,61 @@
 pub fn handle<T, O: Into<Result<http::Response<bytes::Bytes>, ft_sdk::Error>>, H: Handler<T, O>>(
     h: H,
 ) {
-    let req = match current_request() {
+    handle_with_middleware(&[], h)
+}
+
+/// Same as [handle], but runs `middleware` around the handler, see [ft_sdk::Middleware].
+pub fn handle_with_middleware<
+    T,
+    O: Into<Result<http::Response<bytes::Bytes>, ft_sdk::Error>>,
+    H: Handler<T, O>,
+>(
+    middleware: &[&dyn ft_sdk::Middleware],
+    h: H,
+) {
+    serve(middleware, |req| h.call(req).and_then(Into::into))
+}
+
+/// Read the current request, run `f` wrapped in `middleware`, and send the response.
+pub(crate) fn serve<F>(middleware: &[&dyn ft_sdk::Middleware], f: F)
+where
+    F: FnOnce(
+        &mut http::Request<serde_json::Value>,
+    ) -> Result<http::Response<bytes::Bytes>, ft_sdk::Error>,
+{
+    let mut req = match current_request() {
         Ok(v) => v,
         Err(e) => {
-            ft_sdk::println!("Error parsing request: {:?}", e);
-            ft_sdk::error::handle_error(e);
+            ft_sdk::println!("Error parsing request");
+            ft_sdk::http::send_response(ft_sdk::error::handle_error(e));
             return;
         }
     };
-    let resp = h.call(&req).and_then(Into::into).unwrap_or_else(|e| {
-        ft_sdk::println!("Error: {:?}", e);
-        ft_sdk::error::handle_error(e)
-    });
+    let resp = ft_sdk::middleware::run(middleware, &mut req, f);
     ft_sdk::http::send_response(resp);
 }
 
+/// Read the current request, with the body parsed based on its `Content-Type`. The body as sent
+/// is kept in the request extensions, for [ft_sdk::RawBody] and [ft_sdk::Text].
 pub fn current_request() -> Result<http::Request<serde_json::Value>, ft_sdk::Error> {
     let r = ft_sys::http::current_request();
-    let (h, b) = r.into_parts();
-    if b.as_ref() == b"" {
-        return Ok(http::Request::from_parts(h, serde_json::Value::Null));
-    }
-    // todo: what if content type is not application/json?
-    let b = serde_json::from_slice(&b)?;
-    Ok(http::Request::from_parts(h, b))
+    let (mut h, b) = r.into_parts();
+    let body = ft_sdk::from_request::body::parse(&mut h, &b)?;
+    h.extensions.insert(ft_sdk::RawBody(b));
+    let req = http::Request::from_parts(h, body);
+    ft_sdk::from_request::connection::reset();
+    ft_sdk::chr::reset_pending_cookies();
+    ft_sdk::from_request::package::set_current(&req);
+    ft_sdk::session::set_current_client(&req);
+    ft_sdk::auth::current::set_session_id(ft_sdk::from_request::fields::cookie(
+        ft_sdk::auth::SESSION_KEY,
+        &req,
+    ));
+    ft_sdk::analytics::set_current(ft_sdk::from_request::fields::cookie(
+        ft_sdk::analytics::TRACKER_KEY,
+        &req,
+    ));
+    Ok(req)
 }
 
 pub trait Handler<T, O>: Sized {
@@ -59,8 +90,11 @@ where
     T2: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> Result<O, ft_sdk::Error> {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
-        (self)(T1::from_request(req)?, T2::from_request(req)?)
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        errors.finish()?;
+        (self)(t1.unwrap(), t2.unwrap())
     }
 }
 
@@ -72,12 +106,12 @@ where
     T3: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> Result<O, ft_sdk::Error> {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
-        (self)(
-            T1::from_request(req)?,
-            T2::from_request(req)?,
-            T3::from_request(req)?,
-        )
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        errors.finish()?;
+        (self)(t1.unwrap(), t2.unwrap(), t3.unwrap())
     }
 }
 
@@ -90,13 +124,13 @@ where
     T4: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> Result<O, ft_sdk::Error> {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
-        (self)(
-            T1::from_request(req)?,
-            T2::from_request(req)?,
-            T3::from_request(req)?,
-            T4::from_request(req)?,
-        )
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        let t4 = errors.extract::<T4>(req)?;
+        errors.finish()?;
+        (self)(t1.unwrap(), t2.unwrap(), t3.unwrap(), t4.unwrap())
     }
 }
 
@@ -110,13 +144,19 @@ where
     T5: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> Result<O, ft_sdk::Error> {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        let t4 = errors.extract::<T4>(req)?;
+        let t5 = errors.extract::<T5>(req)?;
+        errors.finish()?;
         (self)(
-            T1::from_request(req)?,
-            T2::from_request(req)?,
-            T3::from_request(req)?,
-            T4::from_request(req)?,
-            T5::from_request(req)?,
+            t1.unwrap(),
+            t2.unwrap(),
+            t3.unwrap(),
+            t4.unwrap(),
+            t5.unwrap(),
         )
     }
 }
@@ -132,14 +172,21 @@ where
     T6: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> Result<O, ft_sdk::Error> {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        let t4 = errors.extract::<T4>(req)?;
+        let t5 = errors.extract::<T5>(req)?;
+        let t6 = errors.extract::<T6>(req)?;
+        errors.finish()?;
         (self)(
-            T1::from_request(req)?,
-            T2::from_request(req)?,
-            T3::from_request(req)?,
-            T4::from_request(req)?,
-            T5::from_request(req)?,
-            T6::from_request(req)?,
+            t1.unwrap(),
+            t2.unwrap(),
+            t3.unwrap(),
+            t4.unwrap(),
+            t5.unwrap(),
+            t6.unwrap(),
         )
     }
 }
@@ -156,15 +203,23 @@ where
     T7: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> Result<O, ft_sdk::Error> {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        let t4 = errors.extract::<T4>(req)?;
+        let t5 = errors.extract::<T5>(req)?;
+        let t6 = errors.extract::<T6>(req)?;
+        let t7 = errors.extract::<T7>(req)?;
+        errors.finish()?;
         (self)(
-            T1::from_request(req)?,
-            T2::from_request(req)?,
-            T3::from_request(req)?,
-            T4::from_request(req)?,
-            T5::from_request(req)?,
-            T6::from_request(req)?,
-            T7::from_request(req)?,
+            t1.unwrap(),
+            t2.unwrap(),
+            t3.unwrap(),
+            t4.unwrap(),
+            t5.unwrap(),
+            t6.unwrap(),
+            t7.unwrap(),
         )
     }
 }
@@ -182,16 +237,25 @@ where
     T8: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> Result<O, ft_sdk::Error> {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        let t4 = errors.extract::<T4>(req)?;
+        let t5 = errors.extract::<T5>(req)?;
+        let t6 = errors.extract::<T6>(req)?;
+        let t7 = errors.extract::<T7>(req)?;
+        let t8 = errors.extract::<T8>(req)?;
+        errors.finish()?;
         (self)(
-            T1::from_request(req)?,
-            T2::from_request(req)?,
-            T3::from_request(req)?,
-            T4::from_request(req)?,
-            T5::from_request(req)?,
-            T6::from_request(req)?,
-            T7::from_request(req)?,
-            T8::from_request(req)?,
+            t1.unwrap(),
+            t2.unwrap(),
+            t3.unwrap(),
+            t4.unwrap(),
+            t5.unwrap(),
+            t6.unwrap(),
+            t7.unwrap(),
+            t8.unwrap(),
         )
     }
 }
@@ -211,17 +275,27 @@ where
     T9: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> Result<O, ft_sdk::Error> {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        let t4 = errors.extract::<T4>(req)?;
+        let t5 = errors.extract::<T5>(req)?;
+        let t6 = errors.extract::<T6>(req)?;
+        let t7 = errors.extract::<T7>(req)?;
+        let t8 = errors.extract::<T8>(req)?;
+        let t9 = errors.extract::<T9>(req)?;
+        errors.finish()?;
         (self)(
-            T1::from_request(req)?,
-            T2::from_request(req)?,
-            T3::from_request(req)?,
-            T4::from_request(req)?,
-            T5::from_request(req)?,
-            T6::from_request(req)?,
-            T7::from_request(req)?,
-            T8::from_request(req)?,
-            T9::from_request(req)?,
+            t1.unwrap(),
+            t2.unwrap(),
+            t3.unwrap(),
+            t4.unwrap(),
+            t5.unwrap(),
+            t6.unwrap(),
+            t7.unwrap(),
+            t8.unwrap(),
+            t9.unwrap(),
         )
     }
 }
@@ -242,18 +316,29 @@ where
     T10: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> Result<O, ft_sdk::Error> {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        let t4 = errors.extract::<T4>(req)?;
+        let t5 = errors.extract::<T5>(req)?;
+        let t6 = errors.extract::<T6>(req)?;
+        let t7 = errors.extract::<T7>(req)?;
+        let t8 = errors.extract::<T8>(req)?;
+        let t9 = errors.extract::<T9>(req)?;
+        let t10 = errors.extract::<T10>(req)?;
+        errors.finish()?;
         (self)(
-            T1::from_request(req)?,
-            T2::from_request(req)?,
-            T3::from_request(req)?,
-            T4::from_request(req)?,
-            T5::from_request(req)?,
-            T6::from_request(req)?,
-            T7::from_request(req)?,
-            T8::from_request(req)?,
-            T9::from_request(req)?,
-            T10::from_request(req)?,
+            t1.unwrap(),
+            t2.unwrap(),
+            t3.unwrap(),
+            t4.unwrap(),
+            t5.unwrap(),
+            t6.unwrap(),
+            t7.unwrap(),
+            t8.unwrap(),
+            t9.unwrap(),
+            t10.unwrap(),
         )
     }
 }
@@ -275,19 +360,31 @@ where
     T11: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> Result<O, ft_sdk::Error> {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        let t4 = errors.extract::<T4>(req)?;
+        let t5 = errors.extract::<T5>(req)?;
+        let t6 = errors.extract::<T6>(req)?;
+        let t7 = errors.extract::<T7>(req)?;
+        let t8 = errors.extract::<T8>(req)?;
+        let t9 = errors.extract::<T9>(req)?;
+        let t10 = errors.extract::<T10>(req)?;
+        let t11 = errors.extract::<T11>(req)?;
+        errors.finish()?;
         (self)(
-            T1::from_request(req)?,
-            T2::from_request(req)?,
-            T3::from_request(req)?,
-            T4::from_request(req)?,
-            T5::from_request(req)?,
-            T6::from_request(req)?,
-            T7::from_request(req)?,
-            T8::from_request(req)?,
-            T9::from_request(req)?,
-            T10::from_request(req)?,
-            T11::from_request(req)?,
+            t1.unwrap(),
+            t2.unwrap(),
+            t3.unwrap(),
+            t4.unwrap(),
+            t5.unwrap(),
+            t6.unwrap(),
+            t7.unwrap(),
+            t8.unwrap(),
+            t9.unwrap(),
+            t10.unwrap(),
+            t11.unwrap(),
         )
     }
 }
@@ -310,20 +407,92 @@ where
     T12: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> Result<O, ft_sdk::Error> {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        let t4 = errors.extract::<T4>(req)?;
+        let t5 = errors.extract::<T5>(req)?;
+        let t6 = errors.extract::<T6>(req)?;
+        let t7 = errors.extract::<T7>(req)?;
+        let t8 = errors.extract::<T8>(req)?;
+        let t9 = errors.extract::<T9>(req)?;
+        let t10 = errors.extract::<T10>(req)?;
+        let t11 = errors.extract::<T11>(req)?;
+        let t12 = errors.extract::<T12>(req)?;
+        errors.finish()?;
         (self)(
-            T1::from_request(req)?,
-            T2::from_request(req)?,
-            T3::from_request(req)?,
-            T4::from_request(req)?,
-            T5::from_request(req)?,
-            T6::from_request(req)?,
-            T7::from_request(req)?,
-            T8::from_request(req)?,
-            T9::from_request(req)?,
-            T10::from_request(req)?,
-            T11::from_request(req)?,
-            T12::from_request(req)?,
+            t1.unwrap(),
+            t2.unwrap(),
+            t3.unwrap(),
+            t4.unwrap(),
+            t5.unwrap(),
+            t6.unwrap(),
+            t7.unwrap(),
+            t8.unwrap(),
+            t9.unwrap(),
+            t10.unwrap(),
+            t11.unwrap(),
+            t12.unwrap(),
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    struct Bad<const N: usize>;
+
+    impl<const N: usize> ft_sdk::FromRequest for Bad<N> {
+        fn from_request(_req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+            Err(ft_sdk::single_error(format!("f{N}"), "bad").into())
+        }
+    }
+
+    struct Good;
+
+    impl ft_sdk::FromRequest for Good {
+        fn from_request(_req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+            Ok(Good)
+        }
+    }
+
+    struct Broken;
+
+    impl ft_sdk::FromRequest for Broken {
+        fn from_request(_req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+            Err(ft_sdk::not_found!("nope").into())
+        }
+    }
+
+    fn call<T, H: super::Handler<T, ()>>(h: H) -> Result<(), ft_sdk::Error> {
+        h.call(&http::Request::new(serde_json::Value::Null))
+    }
+
+    #[test]
+    fn collects_all_field_errors() {
+        let e = call(|_: Bad<1>, _: Good, _: Bad<2>| Ok(())).unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<ft_sdk::SpecialError>(),
+            Some(&ft_sdk::SpecialError::Multi(
+                [("f1", "bad"), ("f2", "bad")]
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect()
+            ))
+        );
+    }
+
+    #[test]
+    fn non_field_error_short_circuits() {
+        let e = call(|_: Bad<1>, _: Broken, _: Bad<2>| Ok(())).unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<ft_sdk::SpecialError>(),
+            Some(&ft_sdk::SpecialError::NotFound("nope".to_string()))
+        );
+    }
+
+    #[test]
+    fn all_good() {
+        call(|_: Good, _: Good| Ok(())).unwrap();
+    }
+}
@@ -3,3 +3,266 @@ impl ft_sdk::FromRequest for http::HeaderMap {
         Ok(req.headers().clone())
     }
 }
+
+/// All values of the header `name`, joined with `,`, `None` if the header is missing or not a
+/// valid string.
+fn header_value(name: &str, req: &http::Request<serde_json::Value>) -> Option<String> {
+    let values = req
+        .headers()
+        .get_all(name)
+        .iter()
+        .map(|v| v.to_str().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if values.is_empty() {
+        return None;
+    }
+    Some(values.join(","))
+}
+
+/// The value of the header `NAME`, parsed into `T`.
+///
+/// If the header is missing or can not be parsed, [ft_sdk::SpecialError::BadRequest] is returned.
+/// Use [OptionalHeader] if the header is optional.
+///
+/// ```rust,ignore
+/// #[ft_sdk::data]
+/// fn version(ft_sdk::Header(v): ft_sdk::Header<"x-api-version", u32>) -> ft_sdk::data::Result {
+///     ft_sdk::data::json(v)
+/// }
+/// ```
+#[cfg(feature = "field-extractors")]
+pub struct Header<const NAME: &'static str, T: serde::de::DeserializeOwned = String>(pub T);
+
+#[cfg(feature = "field-extractors")]
+impl<const NAME: &'static str, T: serde::de::DeserializeOwned> ft_sdk::FromRequest
+    for Header<NAME, T>
+{
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        match OptionalHeader::<NAME, T>::from_request(req)?.0 {
+            Some(v) => Ok(Header(v)),
+            None => Err(ft_sdk::bad_request!("missing header: {NAME}").into()),
+        }
+    }
+}
+
+#[cfg(feature = "field-extractors")]
+impl<const NAME: &'static str, T: serde::de::DeserializeOwned> std::ops::Deref for Header<NAME, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "field-extractors")]
+impl<const NAME: &'static str, T: serde::de::DeserializeOwned + std::fmt::Display> std::fmt::Display
+    for Header<NAME, T>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        T::fmt(&self.0, f)
+    }
+}
+
+/// Same as [Header], but `None` if the header is missing. A header that is present but can not
+/// be parsed is still an error.
+#[cfg(feature = "field-extractors")]
+pub struct OptionalHeader<const NAME: &'static str, T: serde::de::DeserializeOwned = String>(
+    pub Option<T>,
+);
+
+#[cfg(feature = "field-extractors")]
+impl<const NAME: &'static str, T: serde::de::DeserializeOwned> ft_sdk::FromRequest
+    for OptionalHeader<NAME, T>
+{
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        let v = match header_value(NAME, req) {
+            Some(v) => v,
+            None => return Ok(OptionalHeader(None)),
+        };
+        ft_sdk::from_request::fields::parse_str(&v)
+            .map(|v| OptionalHeader(Some(v)))
+            .map_err(|e| ft_sdk::bad_request!("invalid header {NAME}: {e}").into())
+    }
+}
+
+#[cfg(feature = "field-extractors")]
+impl<const NAME: &'static str, T: serde::de::DeserializeOwned> std::ops::Deref
+    for OptionalHeader<NAME, T>
+{
+    type Target = Option<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// The token from an `Authorization: Bearer <token>` header.
+///
+/// If the header is missing, or is not a bearer token, [ft_sdk::SpecialError::BadRequest] is
+/// returned.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BearerToken(pub String);
+
+impl ft_sdk::FromRequest for BearerToken {
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        let v = match header_value("authorization", req) {
+            Some(v) => v,
+            None => return Err(ft_sdk::bad_request!("missing header: authorization").into()),
+        };
+        match v.split_once(' ') {
+            Some((scheme, token))
+                if scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty() =>
+            {
+                Ok(BearerToken(token.trim().to_string()))
+            }
+            _ => Err(ft_sdk::bad_request!("authorization header is not a bearer token").into()),
+        }
+    }
+}
+
+/// Split a header like `text/html;q=0.8, application/json` into its values, ordered by their
+/// `q` value, highest first. Values with the same `q` keep the order they were sent in, and values
+/// with `q=0` are dropped.
+fn by_quality(v: &str) -> Vec<String> {
+    let mut values: Vec<(String, f32)> = v
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let value = parts.next()?.trim().to_ascii_lowercase();
+            if value.is_empty() {
+                return None;
+            }
+            let q = parts
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((value, q))
+        })
+        .filter(|(_, q)| *q > 0.0)
+        .collect();
+    values.sort_by(|a, b| b.1.total_cmp(&a.1));
+    values.into_iter().map(|(v, _)| v).collect()
+}
+
+/// The media types from the `Accept` header, most preferred first. A missing header is treated
+/// as `*/*`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accept(pub Vec<String>);
+
+impl ft_sdk::FromRequest for Accept {
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        Ok(Accept(by_quality(
+            &header_value("accept", req).unwrap_or_else(|| "*/*".to_string()),
+        )))
+    }
+}
+
+impl Accept {
+    /// Pick the most preferred of the `available` media types, if any is acceptable.
+    pub fn best<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        self.0.iter().find_map(|range| {
+            available.iter().copied().find(|a| {
+                let a = a.to_ascii_lowercase();
+                range == "*/*"
+                    || *range == a
+                    || range
+                        .strip_suffix("/*")
+                        .is_some_and(|t| a.split('/').next() == Some(t))
+            })
+        })
+    }
+}
+
+/// The language tags from the `Accept-Language` header, most preferred first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AcceptLanguage(pub Vec<String>);
+
+impl ft_sdk::FromRequest for AcceptLanguage {
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        Ok(AcceptLanguage(by_quality(
+            &header_value("accept-language", req).unwrap_or_default(),
+        )))
+    }
+}
+
+impl AcceptLanguage {
+    /// Pick the most preferred of the `available` languages. `en-US` in the header matches `en`
+    /// if `en-US` is not available, and `en` matches `en-GB`.
+    pub fn best<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        self.0.iter().find_map(|range| {
+            let exact = available
+                .iter()
+                .copied()
+                .find(|a| range == "*" || a.eq_ignore_ascii_case(range));
+            exact.or_else(|| {
+                available.iter().copied().find(|a| {
+                    let a = a.to_ascii_lowercase();
+                    range.starts_with(&format!("{a}-")) || a.starts_with(&format!("{range}-"))
+                })
+            })
+        })
+    }
+}
+
+/// The entity tags from the `If-None-Match` header, empty if the header is missing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IfNoneMatch(pub Vec<String>);
+
+impl ft_sdk::FromRequest for IfNoneMatch {
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        Ok(IfNoneMatch(
+            header_value("if-none-match", req)
+                .unwrap_or_default()
+                .split(',')
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect(),
+        ))
+    }
+}
+
+impl IfNoneMatch {
+    /// Check if the client already has `etag`, in which case `304 Not Modified` can be sent.
+    /// Weak and strong tags are compared the same way, as `If-None-Match` requires.
+    pub fn matches(&self, etag: &str) -> bool {
+        let etag = etag.trim_start_matches("W/");
+        self.0
+            .iter()
+            .any(|t| t == "*" || t.trim_start_matches("W/") == etag)
+    }
+}
+
+/// The `Referer` header, if present.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Referer(pub Option<String>);
+
+impl ft_sdk::FromRequest for Referer {
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        Ok(Referer(header_value("referer", req)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn accept() {
+        let a = super::Accept(super::by_quality(
+            "text/html;q=0.8, application/json, image/*;q=0.9, text/plain;q=0",
+        ));
+        assert_eq!(a.0, ["application/json", "image/*", "text/html"]);
+        assert_eq!(a.best(&["text/html", "image/png"]), Some("image/png"));
+        assert_eq!(a.best(&["text/plain"]), None);
+
+        let l = super::AcceptLanguage(super::by_quality("fr-CH, fr;q=0.9, en;q=0.8"));
+        assert_eq!(l.best(&["en", "fr"]), Some("fr"));
+        assert_eq!(l.best(&["en-GB", "de"]), Some("en-GB"));
+    }
+
+    #[test]
+    fn if_none_match() {
+        let m = super::IfNoneMatch(vec!["W/\"a\"".to_string(), "\"b\"".to_string()]);
+        assert!(m.matches("\"a\""));
+        assert!(m.matches("W/\"b\""));
+        assert!(!m.matches("\"c\""));
+    }
+}
@@ -1,9 +1,13 @@
 mod app_url;
+pub(crate) mod body;
 mod config;
+pub(crate) mod connection;
 #[cfg(feature = "field-extractors")]
 mod cookie;
 #[cfg(feature = "field-extractors")]
 mod default;
+mod field_errors;
+pub mod fields;
 mod form;
 pub mod handler;
 mod headers;
@@ -11,35 +15,48 @@ mod headers;
 mod hidden;
 mod host;
 mod json;
+pub mod multipart;
 #[cfg(feature = "field-extractors")]
 mod optional;
-mod package;
+pub(crate) mod package;
 mod path;
+mod path_params;
 #[cfg(feature = "field-extractors")]
 mod query;
 #[cfg(feature = "field-extractors")]
 mod required;
 mod scheme;
+pub(crate) mod session;
+mod user;
 pub mod wrapped_processor;
 
 #[cfg(feature = "field-extractors")]
 pub use {
     app_url::{AppUrl, RequiredAppUrl},
-    cookie::Cookie,
+    cookie::{Cookie, EncryptedCookie, SignedCookie},
     default::Default,
+    headers::{Header, OptionalHeader},
     hidden::Hidden,
+    multipart::{UploadedFile, UploadedFiles},
     optional::Optional,
     query::Query,
     required::Required,
+    user::{RequirePermission, RequireScopes},
 };
 pub use {
+    body::{RawBody, Text},
     config::Config,
+    field_errors::FieldErrors,
     form::Form,
+    headers::{Accept, AcceptLanguage, BearerToken, IfNoneMatch, Referer},
     host::Host,
     json::Json,
     package::{MainPackage, WasmPackage},
     path::Path,
+    path_params::PathParams,
     scheme::Scheme,
+    session::{OptionalSession, Session},
+    user::{CurrentUser, LOGIN_URL_ENV, RequireUser, TWO_FACTOR_URL_ENV},
 };
 
 pub trait FromRequest: Sized {
@@ -54,7 +71,7 @@ impl FromRequest for chrono::DateTime<chrono::Utc> {
 
 impl FromRequest for ft_sdk::Connection {
     fn from_request(_req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
-        Ok(ft_sdk::default_connection()?)
+        ft_sdk::from_request::connection::take()
     }
 }
 
@@ -0,0 +1,359 @@
+//! `multipart/form-data` request bodies, as sent by HTML forms with `enctype="multipart/form-data"`.
+//!
+//! Non-file fields are merged into the JSON object request body, so [ft_sdk::Required],
+//! [ft_sdk::Optional] etc. work the same as for other bodies. Files are kept in the request
+//! extensions, and are read using [ft_sdk::UploadedFile] and [ft_sdk::UploadedFiles].
+
+/// Default maximum size of a single uploaded file, 10MB.
+pub const DEFAULT_MAX_FILE_SIZE: usize = 10 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum MultipartError {
+    #[error("boundary is missing in content-type")]
+    MissingBoundary,
+    #[error("malformed multipart body: {0}")]
+    Malformed(&'static str),
+    #[error("part is missing content-disposition name")]
+    MissingName,
+}
+
+/// A single file from a multipart body.
+#[cfg_attr(not(feature = "field-extractors"), allow(dead_code))]
+#[derive(Clone, Debug)]
+pub(crate) struct FilePart {
+    pub(crate) name: String,
+    pub(crate) file_name: String,
+    pub(crate) content_type: String,
+    pub(crate) bytes: bytes::Bytes,
+}
+
+/// All files in the request, stored in the request extensions.
+#[cfg_attr(not(feature = "field-extractors"), allow(dead_code))]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Files(pub(crate) Vec<FilePart>);
+
+/// Get the `boundary` parameter of a `multipart/form-data` content type.
+pub(crate) fn boundary(content_type: &str) -> Result<String, MultipartError> {
+    content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|p| p.split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, v)| v.trim().trim_matches('"').to_string())
+        .filter(|v| !v.is_empty())
+        .ok_or(MultipartError::MissingBoundary)
+}
+
+/// Parse a multipart body into an object of the non-file fields, and the files.
+///
+/// File inputs left empty by the user are sent by browsers as a part with an empty file name,
+/// these are skipped.
+pub(crate) fn parse(
+    boundary: &str,
+    body: &bytes::Bytes,
+) -> Result<(serde_json::Value, Files), MultipartError> {
+    let delimiter = format!("--{boundary}");
+    let delimiter = delimiter.as_bytes();
+
+    let mut fields = serde_json::Map::new();
+    let mut files = vec![];
+
+    let mut pos =
+        find(body, delimiter, 0).ok_or(MultipartError::Malformed("no boundary"))? + delimiter.len();
+
+    loop {
+        if body[pos..].starts_with(b"--") {
+            break;
+        }
+        pos = skip_crlf(body, pos)?;
+
+        let headers_end = find(body, b"\r\n\r\n", pos)
+            .ok_or(MultipartError::Malformed("part headers not terminated"))?;
+        let headers = std::str::from_utf8(&body[pos..headers_end])
+            .map_err(|_| MultipartError::Malformed("part headers are not utf-8"))?;
+        pos = headers_end + 4;
+
+        let end = find_delimiter(body, delimiter, pos)
+            .ok_or(MultipartError::Malformed("part not terminated"))?;
+        let content = body.slice(pos..end);
+        pos = end + 2 + delimiter.len();
+
+        let mut name = None;
+        let mut file_name = None;
+        let mut content_type = None;
+        for line in headers.split("\r\n") {
+            let (k, v) = match line.split_once(':') {
+                Some(v) => v,
+                None => continue,
+            };
+            if k.trim().eq_ignore_ascii_case("content-disposition") {
+                for (pk, pv) in disposition_params(v) {
+                    match pk.to_ascii_lowercase().as_str() {
+                        "name" => name = Some(pv),
+                        "filename" => file_name = Some(pv),
+                        _ => {}
+                    }
+                }
+            } else if k.trim().eq_ignore_ascii_case("content-type") {
+                content_type = Some(v.trim().to_string());
+            }
+        }
+
+        let name = name.ok_or(MultipartError::MissingName)?;
+        match file_name {
+            Some(f) if f.is_empty() && content.is_empty() => {}
+            Some(file_name) => files.push(FilePart {
+                name,
+                file_name,
+                content_type: content_type
+                    .unwrap_or_else(|| "application/octet-stream".to_string()),
+                bytes: content,
+            }),
+            None => {
+                let value = String::from_utf8_lossy(&content).into_owned();
+                ft_sdk::from_request::body::insert(&mut fields, name, value);
+            }
+        }
+    }
+
+    Ok((serde_json::Value::Object(fields), Files(files)))
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|p| p + from)
+}
+
+/// Find the `\r\n--boundary` that ends the part starting at `from`.
+fn find_delimiter(body: &[u8], delimiter: &[u8], mut from: usize) -> Option<usize> {
+    loop {
+        let p = find(body, b"\r\n", from)?;
+        if body[p + 2..].starts_with(delimiter) {
+            return Some(p);
+        }
+        from = p + 2;
+    }
+}
+
+fn skip_crlf(body: &[u8], pos: usize) -> Result<usize, MultipartError> {
+    // transport padding is allowed after the boundary
+    let mut pos = pos;
+    while matches!(body.get(pos), Some(b' ' | b'\t')) {
+        pos += 1;
+    }
+    if body[pos..].starts_with(b"\r\n") {
+        Ok(pos + 2)
+    } else {
+        Err(MultipartError::Malformed("expected CRLF after boundary"))
+    }
+}
+
+/// Parse `form-data; name="a"; filename="b.txt"` into `(key, value)` pairs.
+fn disposition_params(v: &str) -> Vec<(String, String)> {
+    let mut params = vec![];
+    let mut rest = v;
+    while let Some((_, after)) = rest.split_once(';') {
+        let (k, after) = match after.split_once('=') {
+            Some(v) => v,
+            None => break,
+        };
+        let after = after.trim_start();
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            if let Some((_, c)) = chars.next() {
+                                value.push(c);
+                            }
+                        }
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                (value, &quoted[end..])
+            }
+            None => match after.find(';') {
+                Some(i) => (after[..i].trim().to_string(), &after[i..]),
+                None => (after.trim().to_string(), ""),
+            },
+        };
+        params.push((k.trim().to_string(), value));
+        rest = after;
+    }
+    params
+}
+
+/// A single file uploaded in the `KEY` field of a `multipart/form-data` form.
+///
+/// `MAX_SIZE` is the maximum file size in bytes. `ACCEPT` is a comma separated list of allowed
+/// content types, like the `accept` attribute of `<input type="file">`, e.g.
+/// `"image/png, image/jpeg"` or `"image/*"`.
+///
+/// ```rust,ignore
+/// #[ft_sdk::form]
+/// fn avatar(
+///     file: ft_sdk::UploadedFile<"avatar", { 1024 * 1024 }, "image/*">,
+/// ) -> ft_sdk::form::Result {
+///     ft_sdk::println!("{} {} bytes", file.file_name, file.bytes.len());
+///     ft_sdk::form::reload()
+/// }
+/// ```
+///
+/// Missing files, files that are too large, or not of an accepted type, are reported as errors
+/// for `KEY`. Use [ft_sdk::UploadedFiles] if the file is optional, or the field allows more than
+/// one file.
+#[cfg(feature = "field-extractors")]
+#[derive(Clone, Debug)]
+pub struct UploadedFile<
+    const KEY: &'static str,
+    const MAX_SIZE: usize = DEFAULT_MAX_FILE_SIZE,
+    const ACCEPT: &'static str = "*/*",
+> {
+    /// The file name as sent by the browser. This is chosen by the user, so do not use it as a
+    /// path without sanitising it.
+    pub file_name: String,
+    pub content_type: String,
+    pub bytes: bytes::Bytes,
+}
+
+#[cfg(feature = "field-extractors")]
+impl<const KEY: &'static str, const MAX_SIZE: usize, const ACCEPT: &'static str> ft_sdk::FromRequest
+    for UploadedFile<KEY, MAX_SIZE, ACCEPT>
+{
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        let mut files = UploadedFiles::<KEY, MAX_SIZE, ACCEPT>::from_request(req)?.0;
+        match files.len() {
+            0 => Err(ft_sdk::single_error(KEY, "missing file").into()),
+            1 => Ok(files.remove(0)),
+            _ => Err(ft_sdk::single_error(KEY, "expected a single file").into()),
+        }
+    }
+}
+
+/// All files uploaded in the `KEY` field, possibly none. See [ft_sdk::UploadedFile] for
+/// `MAX_SIZE` and `ACCEPT`, which apply to each file.
+#[cfg(feature = "field-extractors")]
+#[derive(Clone, Debug)]
+pub struct UploadedFiles<
+    const KEY: &'static str,
+    const MAX_SIZE: usize = DEFAULT_MAX_FILE_SIZE,
+    const ACCEPT: &'static str = "*/*",
+>(pub Vec<UploadedFile<KEY, MAX_SIZE, ACCEPT>>);
+
+#[cfg(feature = "field-extractors")]
+impl<const KEY: &'static str, const MAX_SIZE: usize, const ACCEPT: &'static str> ft_sdk::FromRequest
+    for UploadedFiles<KEY, MAX_SIZE, ACCEPT>
+{
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        let files = match req.extensions().get::<Files>() {
+            Some(v) => v,
+            None => return Ok(UploadedFiles(vec![])),
+        };
+
+        let mut v = vec![];
+        for f in files.0.iter().filter(|f| f.name == KEY) {
+            if f.bytes.len() > MAX_SIZE {
+                return Err(ft_sdk::single_error(
+                    KEY,
+                    format!("file is too large, maximum size is {MAX_SIZE} bytes"),
+                )
+                .into());
+            }
+            if !accepts(ACCEPT, &f.content_type) {
+                return Err(ft_sdk::single_error(
+                    KEY,
+                    format!("file type {} is not allowed", f.content_type),
+                )
+                .into());
+            }
+            v.push(UploadedFile {
+                file_name: f.file_name.clone(),
+                content_type: f.content_type.clone(),
+                bytes: f.bytes.clone(),
+            });
+        }
+
+        Ok(UploadedFiles(v))
+    }
+}
+
+/// Check `content_type` against a comma separated list like `"image/*, application/pdf"`.
+#[cfg(feature = "field-extractors")]
+fn accepts(accept: &str, content_type: &str) -> bool {
+    let content_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    accept
+        .split(',')
+        .map(|a| a.trim().to_ascii_lowercase())
+        .any(|a| {
+            a == "*/*"
+                || a == content_type
+                || a.strip_suffix("/*")
+                    .is_some_and(|t| content_type.split('/').next() == Some(t))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    const BODY: &str = "preamble\r\n\
+        --xyz\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        hello\r\nworld\r\n\
+        --xyz\r\n\
+        Content-Disposition: form-data; name=\"tag\"\r\n\r\na\r\n\
+        --xyz\r\n\
+        Content-Disposition: form-data; name=\"tag\"\r\n\r\nb\r\n\
+        --xyz\r\n\
+        Content-Disposition: form-data; name=\"doc\"; filename=\"a \\\"b\\\".txt\"\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        file\r\n--not-the-boundary\r\n\
+        --xyz\r\n\
+        Content-Disposition: form-data; name=\"empty\"; filename=\"\"\r\n\
+        Content-Type: application/octet-stream\r\n\
+        \r\n\
+        \r\n\
+        --xyz--\r\n";
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            super::boundary("multipart/form-data; boundary=\"xyz\"").unwrap(),
+            "xyz"
+        );
+
+        let (fields, files) = super::parse("xyz", &bytes::Bytes::from(BODY)).unwrap();
+        assert_eq!(
+            fields,
+            serde_json::json!({"title": "hello\r\nworld", "tag": ["a", "b"]})
+        );
+        assert_eq!(files.0.len(), 1);
+        assert_eq!(files.0[0].name, "doc");
+        assert_eq!(files.0[0].file_name, "a \"b\".txt");
+        assert_eq!(files.0[0].content_type, "text/plain");
+        assert_eq!(files.0[0].bytes.as_ref(), b"file\r\n--not-the-boundary");
+    }
+
+    #[cfg(feature = "field-extractors")]
+    #[test]
+    fn accepts() {
+        assert!(super::accepts("*/*", "application/pdf"));
+        assert!(super::accepts("image/png, image/*", "IMAGE/JPEG"));
+        assert!(!super::accepts("image/*", "text/plain"));
+    }
+}
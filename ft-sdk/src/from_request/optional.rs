@@ -32,20 +32,6 @@ impl<const KEY: &'static str, T: serde::de::DeserializeOwned> ft_sdk::FromReques
     for Optional<KEY, T>
 {
     fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
-        match req.body() {
-            serde_json::Value::Null => {
-                Err(ft_sdk::single_error(KEY, "body is Null, expected Object").into())
-            }
-            serde_json::Value::Object(map) => {
-                if let Some(value) = map.get(KEY) {
-                    Ok(serde_json::from_value(value.clone())
-                        .map(Some)
-                        .map(Optional)?)
-                } else {
-                    Ok(Optional(None))
-                }
-            }
-            _ => Err(ft_sdk::single_error(KEY, "body is not json object").into()),
-        }
+        ft_sdk::from_request::fields::body_optional(KEY, req).map(Optional)
     }
 }
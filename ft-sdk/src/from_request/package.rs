@@ -45,3 +45,44 @@ impl AsRef<str> for WasmPackage {
         &self.0
     }
 }
+
+thread_local! {
+    static CURRENT_WASM_PACKAGE: std::cell::RefCell<Option<String>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Remember the [WasmPackage] of the current request, called when the request is read. Used to
+/// scope data per app, e.g. `ft_sdk::auth::rbac` roles.
+pub(crate) fn set_current(req: &http::Request<serde_json::Value>) {
+    let p = ft_sdk::FromRequest::from_request(req)
+        .ok()
+        .map(|WasmPackage(p)| p)
+        .filter(|p| !p.is_empty());
+    CURRENT_WASM_PACKAGE.with(|c| *c.borrow_mut() = p);
+}
+
+/// The [WasmPackage] of the current request, `None` outside a request, or if the request does
+/// not say.
+pub(crate) fn current_wasm_package() -> Option<WasmPackage> {
+    CURRENT_WASM_PACKAGE.with(|c| c.borrow().clone().map(WasmPackage))
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn current_wasm_package() {
+        let req = http::Request::builder()
+            .header("x-fastn-wasm-package", "shop.example.com")
+            .body(serde_json::Value::Null)
+            .unwrap();
+        super::set_current(&req);
+        assert_eq!(
+            super::current_wasm_package().map(|p| p.0).as_deref(),
+            Some("shop.example.com")
+        );
+
+        let req = http::Request::new(serde_json::Value::Null);
+        super::set_current(&req);
+        assert!(super::current_wasm_package().is_none());
+    }
+}
@@ -0,0 +1,45 @@
+/// Typed values captured by the `{name}` segments of the route matched by [ft_sdk::Router].
+///
+/// ```rust,ignore
+/// #[derive(serde::Deserialize)]
+/// struct ItemPath {
+///     id: i64,
+/// }
+///
+/// #[ft_sdk::route("/items/{id}/")]
+/// fn item(ft_sdk::PathParams(p): ft_sdk::PathParams<ItemPath>) -> ft_sdk::data::Result {
+///     ft_sdk::data::json(p.id)
+/// }
+/// ```
+///
+/// Values are parsed the same way as query string values, so numbers, booleans and strings all
+/// work. If a value can not be parsed, [ft_sdk::SpecialError::NotFound] is returned, as the path
+/// does not point to anything that exists.
+pub struct PathParams<T: serde::de::DeserializeOwned>(pub T);
+
+impl<T: serde::de::DeserializeOwned> ft_sdk::FromRequest for PathParams<T> {
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        let params = match req.extensions().get::<ft_sdk::router::PathParamsMap>() {
+            Some(v) => v,
+            None => {
+                return Err(ft_sdk::server_error!(
+                    "ft_sdk::PathParams used outside ft_sdk::Router"
+                )
+                .into());
+            }
+        };
+
+        // path params are untyped strings, going via the urlencoded format lets serde_urlencoded
+        // parse them into whatever type the field has.
+        let encoded = serde_urlencoded::to_string(&params.0)?;
+        serde_urlencoded::from_str(&encoded)
+            .map(PathParams)
+            .map_err(|e| ft_sdk::not_found!("invalid path parameter: {e}").into())
+    }
+}
+
+impl<T: serde::de::DeserializeOwned> AsRef<T> for PathParams<T> {
+    fn as_ref(&self) -> &T {
+        &self.0
+    }
+}
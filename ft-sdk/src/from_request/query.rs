@@ -54,57 +54,14 @@ where
 
 impl<const KEY: &'static str> ft_sdk::FromRequest for Query<KEY, String> {
     fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
-        let query = req.uri().query().unwrap_or_default();
-        let args: Vec<(String, String)> = match serde_urlencoded::from_str(query) {
-            Ok(v) => v,
-            Err(_) => {
-                ft_sdk::println!("failed to parse query: {}", query);
-                vec![]
-            }
-        };
-
-        if let Some((_, v)) = args.into_iter().find(|(k, _)| k == KEY) {
-            return Ok(Query(v));
-        }
-
-        if let serde_json::Value::Object(map) = req.body() {
-            if let Some(serde_json::Value::String(s)) = map.get(KEY) {
-                if s.is_empty() {
-                    return Err(ft_sdk::single_error(KEY, "field is empty").into());
-                }
-
-                return Ok(Query(s.to_string()));
-            }
-        }
-
-        Err(ft_sdk::single_error(KEY, format!("{KEY} is missing in input")).into())
+        ft_sdk::from_request::fields::query(KEY, req).map(Query)
     }
 }
 
 impl<const KEY: &'static str> ft_sdk::FromRequest for Query<KEY, Option<String>> {
     fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
-        let query = req.uri().query().unwrap_or_default();
-        let args: Vec<(String, String)> = match serde_urlencoded::from_str(query) {
-            Ok(v) => v,
-            Err(_) => {
-                return Ok(Query(None));
-            }
-        };
-
-        if let Some((_, v)) = args.into_iter().find(|(k, _)| k == KEY) {
-            return Ok(Query(Some(v)));
-        }
-
-        if let serde_json::Value::Object(map) = req.body() {
-            if let Some(serde_json::Value::String(s)) = map.get(KEY) {
-                if s.is_empty() {
-                    return Ok(Query(None));
-                }
-
-                return Ok(Query(Some(s.to_string())));
-            }
-        }
-
-        Ok(Query(None))
+        Ok(Query(ft_sdk::from_request::fields::query_optional(
+            KEY, req,
+        )))
     }
 }
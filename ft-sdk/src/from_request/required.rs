@@ -44,35 +44,11 @@ impl<const KEY: &'static str, T: serde::de::DeserializeOwned> std::ops::DerefMut
     }
 }
 
-// We need `'static` here so that we can use the `std::any::TypeId::of()`. I read this thread
-// to understand what it means: https://www.reddit.com/r/learnrust/comments/12fpu7m/what_does_static_mean_in_a_trait/
-// types like i32, Vec<i32>, String as `'static`, but not say `&'a str`. We are using
-// serde::de::DeserializeOwned as a trait, which feels is also "owned" and hence `'static`, so
-// adding 'static here does not limit the types that can be used with this trait (beyond what
-// serde::de::DeserializeOwned already limits).
+// `'static` is needed by ft_sdk::from_request::fields::body, see the comment there.
 impl<const KEY: &'static str, T: serde::de::DeserializeOwned + 'static> ft_sdk::FromRequest
     for Required<KEY, T>
 {
     fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
-        match req.body() {
-            serde_json::Value::Null => {
-                Err(ft_sdk::single_error(KEY, "body is Null, expected Object").into())
-            }
-            serde_json::Value::Object(map) => {
-                if let Some(value) = map.get(KEY) {
-                    if std::any::TypeId::of::<T>() == std::any::TypeId::of::<String>() {
-                        if let serde_json::Value::String(s) = value {
-                            if s.is_empty() {
-                                return Err(ft_sdk::single_error(KEY, "field is empty").into());
-                            }
-                        }
-                    }
-                    Ok(serde_json::from_value(value.clone()).map(Required)?)
-                } else {
-                    Err(ft_sdk::single_error(KEY, "missing field").into())
-                }
-            }
-            _ => Err(ft_sdk::single_error(KEY, "body is not json object").into()),
-        }
+        ft_sdk::from_request::fields::body(KEY, req).map(Required)
     }
 }
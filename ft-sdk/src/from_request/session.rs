@@ -0,0 +1,69 @@
+/// The session of the current request, `None` if there is no session cookie, or it does not name
+/// a session that exists and has not expired, see [ft_sdk::session::expiry].
+#[derive(Debug)]
+pub struct OptionalSession(pub Option<ft_sdk::SessionID>);
+
+impl ft_sdk::FromRequest for OptionalSession {
+    fn from_request(_req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        ft_sdk::from_request::connection::with(|conn| Ok(OptionalSession(current(conn)?)))
+    }
+}
+
+/// The session of the current request. If there is none, see [OptionalSession], an anonymous
+/// session is created, and its cookie is set on the response.
+///
+/// ```rust,ignore
+/// #[ft_sdk::data]
+/// fn cart(
+///     ft_sdk::Session(sid): ft_sdk::Session,
+///     mut conn: ft_sdk::Connection,
+/// ) -> ft_sdk::data::Result {
+///     let items: Vec<String> = sid.data(&mut conn)?.get_key("cart").unwrap_or_default();
+///     ft_sdk::data::json(items)
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Session(pub ft_sdk::SessionID);
+
+impl ft_sdk::FromRequest for Session {
+    fn from_request(_req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        ft_sdk::from_request::connection::with(|conn| Ok(Session(current_or_create(conn)?)))
+    }
+}
+
+impl std::ops::Deref for Session {
+    type Target = ft_sdk::SessionID;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// The session of the current request, see [OptionalSession].
+pub(crate) fn current(
+    conn: &mut ft_sdk::Connection,
+) -> Result<Option<ft_sdk::SessionID>, ft_sdk::Error> {
+    // not the cookie, so a session created or rotated earlier in the request is used
+    let sid = match ft_sdk::auth::session_id() {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let exists = ft_sdk::session::expiry::resolve(conn, sid.0.as_str())?;
+
+    Ok(exists.then_some(sid))
+}
+
+/// The session of the current request, created if there is none, see [Session].
+pub(crate) fn current_or_create(
+    conn: &mut ft_sdk::Connection,
+) -> Result<ft_sdk::SessionID, ft_sdk::Error> {
+    if let Some(sid) = current(conn)? {
+        return Ok(sid);
+    }
+
+    let sid = ft_sdk::SessionID::create(conn, None, None)?;
+    ft_sdk::auth::current::set_session_id(Some(sid.0.clone()));
+    ft_sdk::chr::add_pending_cookie(sid.cookie());
+
+    Ok(sid)
+}
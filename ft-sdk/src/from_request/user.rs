@@ -0,0 +1,247 @@
+/// Environment variable holding the url [RequireUser] sends browsers to when they are not logged
+/// in. The path they came from is appended as the `next` query parameter.
+pub const LOGIN_URL_ENV: &str = "LOGIN_URL";
+/// Same as [LOGIN_URL_ENV], for sessions where the user still has to enter their second factor,
+/// see [ft_sdk::auth::TWO_FACTOR_PENDING_KEY].
+pub const TWO_FACTOR_URL_ENV: &str = "TWO_FACTOR_URL";
+
+/// The user logged in to the session of the current request, `None` if not logged in.
+///
+/// ```rust,ignore
+/// #[ft_sdk::data]
+/// fn greeting(ft_sdk::CurrentUser(user): ft_sdk::CurrentUser) -> ft_sdk::data::Result {
+///     match user {
+///         Some(u) => ft_sdk::data::json(format!("hello {}", u.name)),
+///         None => ft_sdk::data::json("hello stranger"),
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CurrentUser(pub Option<ft_sdk::UserData>);
+
+impl ft_sdk::FromRequest for CurrentUser {
+    fn from_request(_req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        ft_sdk::from_request::connection::with(|conn| {
+            Ok(CurrentUser(ft_sdk::auth::user_data_by_session(conn)?))
+        })
+    }
+}
+
+/// The user logged in to the session of the current request.
+///
+/// If the user is not logged in, [ft_sdk::SpecialError::Unauthorised] is returned. For `GET`
+/// requests from a browser, if the [LOGIN_URL_ENV] environment variable is set, the browser is
+/// redirected to the login page instead.
+///
+/// If the user has logged in, but not yet entered their second factor, the message says so,
+/// and browsers are sent to [TWO_FACTOR_URL_ENV] instead.
+#[derive(Debug)]
+pub struct RequireUser(pub ft_sdk::UserData);
+
+impl ft_sdk::FromRequest for RequireUser {
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        if let CurrentUser(Some(u)) = CurrentUser::from_request(req)? {
+            return Ok(RequireUser(u));
+        }
+
+        let pending = ft_sdk::from_request::connection::with(|conn| {
+            Ok(ft_sdk::auth::two_factor_pending_user(conn)?)
+        })?;
+        let (env, msg) = match pending {
+            Some(_) => (TWO_FACTOR_URL_ENV, "second factor required"),
+            None => (LOGIN_URL_ENV, "login required"),
+        };
+
+        let url = ft_sys::env::var(env.to_string()).filter(|v| !v.is_empty());
+        match url {
+            Some(url) if is_browser(req) => {
+                Err(ft_sdk::SpecialError::Redirect(login_redirect(&url, req.uri())).into())
+            }
+            _ => Err(ft_sdk::unauthorised!("{msg}").into()),
+        }
+    }
+}
+
+impl std::ops::Deref for RequireUser {
+    type Target = ft_sdk::UserData;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Requires the current session to have been granted scopes by an auth provider, see
+/// [ft_sdk::auth::Scope]. `SCOPES` is the provider id, a `:`, and the space separated scopes, as
+/// in OAuth2:
+///
+/// ```rust,ignore
+/// #[ft_sdk::data]
+/// fn repos(
+///     _: ft_sdk::RequireScopes<"github:repo read:org">,
+///     user: ft_sdk::RequireUser,
+/// ) -> ft_sdk::data::Result {
+///     todo!()
+/// }
+/// ```
+///
+/// If any scope is missing, [ft_sdk::SpecialError::MissingScopes] is returned with the missing
+/// scopes, so the user can be asked to log in again with them. This does not check that the user
+/// is logged in, use [RequireUser] for that.
+///
+/// A `SCOPES` without the provider, e.g. `RequireScopes<"repo">`, fails the build.
+#[cfg(feature = "field-extractors")]
+#[derive(Debug)]
+pub struct RequireScopes<const SCOPES: &'static str>;
+
+#[cfg(feature = "field-extractors")]
+impl<const SCOPES: &'static str> ft_sdk::FromRequest for RequireScopes<SCOPES> {
+    fn from_request(_req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        // evaluated when the extractor is used, so a `SCOPES` without a provider fails the build
+        const {
+            assert!(
+                has_provider(SCOPES),
+                "RequireScopes: expected `<provider>:<scopes>`"
+            )
+        };
+        let (provider, required) = parse_scopes(SCOPES)?;
+        let granted = ft_sdk::from_request::connection::with(|conn| {
+            Ok(ft_sdk::auth::session_scopes(conn, provider)?)
+        })?;
+
+        let missing = ft_sdk::auth::missing_scopes(&granted, &required);
+        if missing.is_empty() {
+            return Ok(RequireScopes);
+        }
+
+        Err(ft_sdk::SpecialError::MissingScopes {
+            provider: provider.to_string(),
+            scopes: missing.into_iter().map(|s| s.0).collect(),
+        }
+        .into())
+    }
+}
+
+/// The logged-in user, who has to have `PERMISSION` through one of their roles, see
+/// [ft_sdk::auth::rbac].
+///
+/// ```rust,ignore
+/// #[ft_sdk::form]
+/// fn delete_item(
+///     user: ft_sdk::RequirePermission<"items.delete">,
+///     mut conn: ft_sdk::Connection,
+/// ) -> ft_sdk::form::Result {
+///     todo!()
+/// }
+/// ```
+///
+/// If the user is not logged in, this fails like [RequireUser]. If they lack the permission,
+/// [ft_sdk::SpecialError::Forbidden] is returned.
+#[cfg(feature = "field-extractors")]
+#[derive(Debug)]
+pub struct RequirePermission<const PERMISSION: &'static str>(pub ft_sdk::UserData);
+
+#[cfg(feature = "field-extractors")]
+impl<const PERMISSION: &'static str> ft_sdk::FromRequest for RequirePermission<PERMISSION> {
+    fn from_request(req: &http::Request<serde_json::Value>) -> Result<Self, ft_sdk::Error> {
+        let RequireUser(user) = RequireUser::from_request(req)?;
+        let allowed = ft_sdk::from_request::connection::with(|conn| {
+            Ok(ft_sdk::auth::rbac::user_has_permission(
+                conn,
+                &ft_sdk::UserId(user.id),
+                PERMISSION,
+            )?)
+        })?;
+
+        if !allowed {
+            return Err(ft_sdk::forbidden!("missing permission {PERMISSION}").into());
+        }
+        Ok(RequirePermission(user))
+    }
+}
+
+#[cfg(feature = "field-extractors")]
+impl<const PERMISSION: &'static str> std::ops::Deref for RequirePermission<PERMISSION> {
+    type Target = ft_sdk::UserData;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Split `"github:repo read:org"` into the provider and its scopes. Only the first `:` separates
+/// the provider, scopes may contain `:` too.
+#[cfg_attr(not(feature = "field-extractors"), allow(dead_code))]
+fn parse_scopes(s: &str) -> Result<(&str, Vec<ft_sdk::auth::Scope>), ft_sdk::SpecialError> {
+    let (provider, scopes) = s
+        .split_once(':')
+        .filter(|(p, _)| !p.is_empty())
+        .ok_or_else(|| {
+            ft_sdk::server_error!("RequireScopes: expected `<provider>:<scopes>`, got `{s}`")
+        })?;
+    Ok((
+        provider,
+        scopes.split_whitespace().map(Into::into).collect(),
+    ))
+}
+
+/// [parse_scopes] would find a provider in `s`.
+#[cfg_attr(not(feature = "field-extractors"), allow(dead_code))]
+const fn has_provider(s: &str) -> bool {
+    let b = s.as_bytes();
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b':' {
+            return i > 0;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// A page load, not a form submission or an API call, which can not follow the redirect.
+fn is_browser(req: &http::Request<serde_json::Value>) -> bool {
+    req.method() == http::Method::GET && !ft_sdk::error::wants_json(req)
+}
+
+fn login_redirect(login_url: &str, uri: &http::Uri) -> String {
+    let next = uri.path_and_query().map(|v| v.as_str()).unwrap_or("/");
+    let sep = if login_url.contains('?') { '&' } else { '?' };
+    // serializing a single string pair can not fail
+    let query = serde_urlencoded::to_string([("next", next)]).unwrap_or_default();
+    format!("{login_url}{sep}{query}")
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn login_redirect() {
+        let uri: http::Uri = "/orders/?page=2".parse().unwrap();
+        assert_eq!(
+            super::login_redirect("/login/", &uri),
+            "/login/?next=%2Forders%2F%3Fpage%3D2"
+        );
+        assert_eq!(
+            super::login_redirect("/auth/?provider=email", &uri),
+            "/auth/?provider=email&next=%2Forders%2F%3Fpage%3D2"
+        );
+    }
+
+    #[test]
+    fn parse_scopes() {
+        let (provider, scopes) = super::parse_scopes("github:repo read:org").unwrap();
+        assert_eq!(provider, "github");
+        assert_eq!(scopes, ["repo".into(), "read:org".into()]);
+
+        let granted = ["read:org".into(), "user".into()];
+        assert_eq!(
+            ft_sdk::auth::missing_scopes(&granted, &scopes),
+            ["repo".into()]
+        );
+
+        for s in ["repo", ":repo"] {
+            assert!(!super::has_provider(s));
+            assert!(super::parse_scopes(s).is_err());
+        }
+        assert!(super::has_provider("github:"));
+    }
+}
@@ -1,17 +1,13 @@
 pub fn handle<T, H: WrappedHandler<T>>(h: H) {
-    let req = match ft_sdk::from_request::handler::current_request() {
-        Ok(v) => v,
-        Err(e) => {
-            ft_sdk::println!("Error parsing request: {:?}", e);
-            ft_sdk::error::handle_error(e);
-            return;
-        }
-    };
-    let resp = h.call(&req).and_then(Into::into).unwrap_or_else(|e| {
-        ft_sdk::println!("Error: {:?}", e);
-        ft_sdk::error::handle_error(e)
-    });
-    ft_sdk::http::send_response(resp);
+    handle_with_middleware(&[], h)
+}
+
+/// Same as [handle], but runs `middleware` around the handler, see [ft_sdk::Middleware].
+pub fn handle_with_middleware<T, H: WrappedHandler<T>>(
+    middleware: &[&dyn ft_sdk::Middleware],
+    h: H,
+) {
+    ft_sdk::from_request::handler::serve(middleware, |req| h.call(req).and_then(Into::into))
 }
 
 pub trait WrappedHandler<T>: Sized {
@@ -59,9 +55,12 @@ where
     T2: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> ft_sdk::processor::Result {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
-        let mut t = T1::from_request(req)?;
-        let o = (self)(&mut t, T2::from_request(req)?)?;
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        errors.finish()?;
+        let mut t = t1.unwrap();
+        let o = (self)(&mut t, t2.unwrap())?;
         wrap(t, o)
     }
 }
@@ -74,9 +73,13 @@ where
     T3: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> ft_sdk::processor::Result {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
-        let mut t = T1::from_request(req)?;
-        let o = (self)(&mut t, T2::from_request(req)?, T3::from_request(req)?)?;
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        errors.finish()?;
+        let mut t = t1.unwrap();
+        let o = (self)(&mut t, t2.unwrap(), t3.unwrap())?;
         wrap(t, o)
     }
 }
@@ -90,14 +93,14 @@ where
     T4: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> ft_sdk::processor::Result {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
-        let mut t = T1::from_request(req)?;
-        let o = (self)(
-            &mut t,
-            T2::from_request(req)?,
-            T3::from_request(req)?,
-            T4::from_request(req)?,
-        )?;
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        let t4 = errors.extract::<T4>(req)?;
+        errors.finish()?;
+        let mut t = t1.unwrap();
+        let o = (self)(&mut t, t2.unwrap(), t3.unwrap(), t4.unwrap())?;
         wrap(t, o)
     }
 }
@@ -112,15 +115,15 @@ where
     T5: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> ft_sdk::processor::Result {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
-        let mut t = T1::from_request(req)?;
-        let o = (self)(
-            &mut t,
-            T2::from_request(req)?,
-            T3::from_request(req)?,
-            T4::from_request(req)?,
-            T5::from_request(req)?,
-        )?;
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        let t4 = errors.extract::<T4>(req)?;
+        let t5 = errors.extract::<T5>(req)?;
+        errors.finish()?;
+        let mut t = t1.unwrap();
+        let o = (self)(&mut t, t2.unwrap(), t3.unwrap(), t4.unwrap(), t5.unwrap())?;
         wrap(t, o)
     }
 }
@@ -136,15 +139,22 @@ where
     T6: ft_sdk::FromRequest,
 {
     fn call(self, req: &http::Request<serde_json::Value>) -> ft_sdk::processor::Result {
-        // TODO: try to parse both t1 and t2 and return result for both together to clients
-        let mut t = T1::from_request(req)?;
+        let mut errors = ft_sdk::from_request::FieldErrors::default();
+        let t1 = errors.extract::<T1>(req)?;
+        let t2 = errors.extract::<T2>(req)?;
+        let t3 = errors.extract::<T3>(req)?;
+        let t4 = errors.extract::<T4>(req)?;
+        let t5 = errors.extract::<T5>(req)?;
+        let t6 = errors.extract::<T6>(req)?;
+        errors.finish()?;
+        let mut t = t1.unwrap();
         let o = (self)(
             &mut t,
-            T2::from_request(req)?,
-            T3::from_request(req)?,
-            T4::from_request(req)?,
-            T5::from_request(req)?,
-            T6::from_request(req)?,
+            t2.unwrap(),
+            t3.unwrap(),
+            t4.unwrap(),
+            t5.unwrap(),
+            t6.unwrap(),
         )?;
         wrap(t, o)
     }
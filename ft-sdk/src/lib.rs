@@ -11,33 +11,49 @@
 
 extern crate self as ft_sdk;
 
+pub mod analytics;
 pub mod auth;
 pub mod chr;
+pub mod cookie;
 mod crypto;
+pub mod csrf;
 pub mod data;
 mod error;
+pub mod flash;
 pub mod form;
 pub mod from_request;
+pub mod middleware;
 pub mod processor;
 mod rng;
+pub mod router;
 pub mod schema;
 pub mod session;
 pub mod utils;
 mod uuid;
 
+pub use analytics::Tracker;
 pub use anyhow::{Context, Error, anyhow, bail, ensure};
 pub use auth::UserId;
 pub use crypto::{DecryptionError, EncryptedString, PlainText};
-pub use error::{SpecialError, not_found_, server_error_, single_error, unauthorised_};
-#[cfg(feature = "field-extractors")]
+pub use csrf::CsrfToken;
+pub use error::{
+    SpecialError, bad_request_, conflict_, forbidden_, gone_, is_production, not_found_,
+    payload_too_large_, server_error_, single_error, too_many_requests_, unauthorised_,
+    unprocessable_entity_,
+};
+pub use flash::{Flash, Flashes};
 pub use from_request::{
-    AppUrl, Cookie, Default, Hidden, Optional, Query, Required, RequiredAppUrl,
+    Accept, AcceptLanguage, BearerToken, Config, CurrentUser, Form, FromRequest, Host, IfNoneMatch,
+    Json, MainPackage, OptionalSession, Path, PathParams, RawBody, Referer, RequireUser, Scheme,
+    Session, Text, WasmPackage, WrappedFromRequest,
 };
+#[cfg(feature = "field-extractors")]
 pub use from_request::{
-    Config, Form, FromRequest, Host, Json, MainPackage, Path, Scheme, WasmPackage,
-    WrappedFromRequest,
+    AppUrl, Cookie, Default, EncryptedCookie, Header, Hidden, Optional, OptionalHeader, Query,
+    RequirePermission, RequireScopes, Required, RequiredAppUrl, SignedCookie, UploadedFile,
+    UploadedFiles,
 };
-pub use ft_derive::{data, form, processor, wrapped_processor};
+pub use ft_derive::{FromRequest, data, form, processor, route, router, wrapped_processor};
 #[cfg(feature = "postgres")]
 pub use ft_sys::PgConnection;
 pub use ft_sys::{ConnectionError, UserData, email, env, http, println};
@@ -46,10 +62,18 @@ pub use ft_sys::{Sqlite, SqliteConnection};
 pub use ft_sys_shared::{
     CancelEmailError, Email, EmailAddress, EmailContent, EmailHandle, RenderedEmail, SendEmailError,
 };
+pub use middleware::Middleware;
 pub use rng::Rng;
+pub use router::{Route, Router};
 pub use session::{SessionData, SessionID};
 pub use uuid::{uuid, uuid_without_dashes};
 
+#[doc(hidden)]
+pub mod __private {
+    // used by code generated by ft-derive
+    pub use {http, serde_json};
+}
+
 pub type FrontendData = std::collections::HashMap<String, serde_json::Value>;
 pub type FormError = std::collections::HashMap<String, String>;
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,170 @@
+//! Hooks that run around every handler, for cross-cutting concerns like login guards, request
+//! logging, CORS or security headers.
+//!
+//! Middleware is attached to an entrypoint using the `middleware` argument:
+//!
+//! ```rust,ignore
+//! #[ft_sdk::data(middleware = [ft_sdk::middleware::SecurityHeaders])]
+//! fn profile() -> ft_sdk::data::Result {
+//!     ft_sdk::data::json("hello")
+//! }
+//! ```
+//!
+//! To use the same list for every entrypoint in a crate, define it once as a `const` and pass
+//! that instead:
+//!
+//! ```rust,ignore
+//! const MIDDLEWARE: &[&dyn ft_sdk::Middleware] = &[&ft_sdk::middleware::SecurityHeaders];
+//!
+//! #[ft_sdk::form(middleware = MIDDLEWARE)]
+//! fn save() -> ft_sdk::form::Result {
+//!     ft_sdk::form::reload()
+//! }
+//! ```
+//!
+//! [ft_sdk::Router] takes middleware using [ft_sdk::Router::with_middleware].
+
+pub trait Middleware {
+    /// Called before any extractor runs. The request can be modified, e.g. to add extensions for
+    /// the extractors to read.
+    ///
+    /// Return `Ok(Some(response))` to skip the handler and respond right away, or an error
+    /// (e.g. `ft_sdk::unauthorised!()`) to respond with that error.
+    fn before(
+        &self,
+        _req: &mut http::Request<serde_json::Value>,
+    ) -> ft_sdk::Result<Option<http::Response<bytes::Bytes>>> {
+        Ok(None)
+    }
+
+    /// Called with the response, including error responses and responses returned by `before`
+    /// of some middleware. Middleware is called in the reverse order for `after`, and only if its
+    /// `before` was called.
+    fn after(
+        &self,
+        _req: &http::Request<serde_json::Value>,
+        resp: http::Response<bytes::Bytes>,
+    ) -> http::Response<bytes::Bytes> {
+        resp
+    }
+}
+
+/// Run `f` wrapped in `middleware`, converting errors to responses.
+pub(crate) fn run<F>(
+    middleware: &[&dyn Middleware],
+    req: &mut http::Request<serde_json::Value>,
+    f: F,
+) -> http::Response<bytes::Bytes>
+where
+    F: FnOnce(
+        &mut http::Request<serde_json::Value>,
+    ) -> ft_sdk::Result<http::Response<bytes::Bytes>>,
+{
+    let mut resp = None;
+    let mut called = 0;
+
+    for m in middleware {
+        called += 1;
+        match m.before(req) {
+            Ok(None) => continue,
+            Ok(Some(r)) => resp = Some(r),
+            Err(e) => resp = Some(ft_sdk::error::handle_error_for(req, e)),
+        }
+        break;
+    }
+
+    let mut resp =
+        resp.unwrap_or_else(|| f(req).unwrap_or_else(|e| ft_sdk::error::handle_error_for(req, e)));
+
+    for m in middleware[..called].iter().rev() {
+        resp = m.after(req, resp);
+    }
+
+    resp
+}
+
+/// Adds headers that tell browsers to not sniff content types, to not render the response in a
+/// frame, and to only send the origin as referrer to other sites.
+///
+/// Headers already set by the handler are not overwritten.
+pub struct SecurityHeaders;
+
+impl Middleware for SecurityHeaders {
+    fn after(
+        &self,
+        _req: &http::Request<serde_json::Value>,
+        mut resp: http::Response<bytes::Bytes>,
+    ) -> http::Response<bytes::Bytes> {
+        let headers = resp.headers_mut();
+        for (k, v) in [
+            (http::header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+            (http::header::X_FRAME_OPTIONS, "DENY"),
+            (
+                http::header::REFERRER_POLICY,
+                "strict-origin-when-cross-origin",
+            ),
+        ] {
+            headers
+                .entry(k)
+                .or_insert(http::HeaderValue::from_static(v));
+        }
+        resp
+    }
+}
+
+#[cfg(test)]
+mod test {
+    struct Tag(&'static str);
+
+    impl super::Middleware for Tag {
+        fn after(
+            &self,
+            _req: &http::Request<serde_json::Value>,
+            mut resp: http::Response<bytes::Bytes>,
+        ) -> http::Response<bytes::Bytes> {
+            let body = format!("{}{}", String::from_utf8_lossy(resp.body()), self.0);
+            *resp.body_mut() = body.into();
+            resp
+        }
+    }
+
+    struct Deny;
+
+    impl super::Middleware for Deny {
+        fn before(
+            &self,
+            _req: &mut http::Request<serde_json::Value>,
+        ) -> ft_sdk::Result<Option<http::Response<bytes::Bytes>>> {
+            Err(ft_sdk::unauthorised!("login required").into())
+        }
+    }
+
+    fn run(middleware: &[&dyn super::Middleware]) -> http::Response<bytes::Bytes> {
+        let mut req = http::Request::new(serde_json::Value::Null);
+        super::run(middleware, &mut req, |_| {
+            Ok(http::Response::new(bytes::Bytes::from("handler;")))
+        })
+    }
+
+    #[test]
+    fn after_runs_in_reverse() {
+        let r = run(&[&Tag("a;"), &Tag("b;")]);
+        assert_eq!(r.body().as_ref(), b"handler;b;a;");
+    }
+
+    #[test]
+    fn before_short_circuits() {
+        let r = run(&[&Tag("a;"), &Deny, &Tag("b;")]);
+        assert_eq!(r.status(), http::StatusCode::UNAUTHORIZED);
+        assert_eq!(r.body().as_ref(), b"unauthorised: login required\na;");
+    }
+
+    #[test]
+    fn security_headers() {
+        let r = run(&[&super::SecurityHeaders]);
+        assert_eq!(
+            r.headers().get(http::header::X_FRAME_OPTIONS),
+            Some(&http::HeaderValue::from_static("DENY"))
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! Dispatch requests to handlers based on the request path and method.
+//!
+//! Without a router every handler is its own wasm export (`<name>__entrypoint`), and the host
+//! decides which one to call. With a router, a single export can serve many paths:
+//!
+//! ```rust,ignore
+//! #[derive(serde::Deserialize)]
+//! struct ItemPath {
+//!     id: i64,
+//! }
+//!
+//! #[ft_sdk::route("/items/{id}/", method = "POST")]
+//! fn update_item(
+//!     ft_sdk::PathParams(p): ft_sdk::PathParams<ItemPath>,
+//! ) -> ft_sdk::form::Result {
+//!     ft_sdk::form::redirect(format!("/items/{}/", p.id))
+//! }
+//!
+//! #[ft_sdk::route("/items/", method = "GET")]
+//! fn list_items() -> ft_sdk::data::Result {
+//!     ft_sdk::data::json(vec![1, 2, 3])
+//! }
+//!
+//! // exports `api__entrypoint`, which dispatches to the two handlers above
+//! ft_sdk::router!(api, update_item, list_items);
+//! ```
+//!
+//! Routes are tried in the order they are added, and the first one matching both the path and
+//! the method wins. Trailing slashes are ignored when matching, so `/items/1` and `/items/1/`
+//! both match `/items/{id}/`. If no route matches the path, [ft_sdk::SpecialError::NotFound] is
+//! returned, and if some route matches the path but not the method,
+//! [ft_sdk::SpecialError::MethodNotAllowed] is returned.
+
+type BoxedHandler =
+    Box<dyn Fn(&http::Request<serde_json::Value>) -> ft_sdk::Result<http::Response<bytes::Bytes>>>;
+
+/// A single route, created by the `#[ft_sdk::route]` attribute, or by [Route::new].
+pub struct Route {
+    method: Option<http::Method>,
+    segments: Vec<Segment>,
+    handler: BoxedHandler,
+}
+
+#[derive(Debug)]
+enum Segment {
+    Static(String),
+    Param(String),
+}
+
+impl Route {
+    /// Create a route for `pattern`. If `method` is `None`, the route matches any method.
+    ///
+    /// `pattern` is a `/` separated path, where `{name}` segments capture the corresponding part
+    /// of the request path. The captured values are available to the handler using
+    /// [ft_sdk::PathParams].
+    ///
+    /// # Panics
+    ///
+    /// If `method` is not a valid HTTP method, or if `pattern` has an unterminated `{`.
+    pub fn new<T, O, H>(method: Option<&str>, pattern: &str, handler: H) -> Route
+    where
+        O: Into<ft_sdk::Result<http::Response<bytes::Bytes>>>,
+        H: ft_sdk::from_request::handler::Handler<T, O> + Copy + 'static,
+    {
+        Route {
+            method: method.map(|m| {
+                http::Method::from_bytes(m.to_ascii_uppercase().as_bytes())
+                    .unwrap_or_else(|_| panic!("invalid http method: {m}"))
+            }),
+            segments: parse_pattern(pattern),
+            handler: Box::new(move |req| handler.call(req).and_then(Into::into)),
+        }
+    }
+
+    fn match_path(&self, path: &[String]) -> Option<PathParamsMap> {
+        if path.len() != self.segments.len() {
+            return None;
+        }
+
+        let mut params = vec![];
+        for (segment, part) in self.segments.iter().zip(path) {
+            match segment {
+                Segment::Static(s) if s == part => {}
+                Segment::Static(_) => return None,
+                Segment::Param(name) => params.push((name.clone(), part.clone())),
+            }
+        }
+
+        Some(PathParamsMap(params))
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    split_path(pattern)
+        .map(|s| match s.strip_prefix('{') {
+            Some(rest) => match rest.strip_suffix('}') {
+                Some(name) => Segment::Param(name.to_string()),
+                None => panic!("unterminated `{{` in route pattern: {pattern}"),
+            },
+            None => Segment::Static(s.to_string()),
+        })
+        .collect()
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty())
+}
+
+/// The values captured by `{name}` segments of the matched route, stored in the request
+/// extensions for [ft_sdk::PathParams] to read.
+#[derive(Clone, Debug)]
+pub(crate) struct PathParamsMap(pub(crate) Vec<(String, String)>);
+
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    middleware: Vec<Box<dyn ft_sdk::Middleware>>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Add a route, usually created by the `#[ft_sdk::route]` attribute.
+    pub fn with_route(mut self, route: Route) -> Router {
+        self.routes.push(route);
+        self
+    }
+
+    /// Run `middleware` around every route, see [ft_sdk::Middleware]. Middleware runs before the
+    /// route is matched, so it also wraps the 404 and 405 responses.
+    pub fn with_middleware<M: ft_sdk::Middleware + 'static>(mut self, middleware: M) -> Router {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    /// Add a route that matches any method.
+    pub fn route<T, O, H>(self, pattern: &str, handler: H) -> Router
+    where
+        O: Into<ft_sdk::Result<http::Response<bytes::Bytes>>>,
+        H: ft_sdk::from_request::handler::Handler<T, O> + Copy + 'static,
+    {
+        self.with_route(Route::new(None, pattern, handler))
+    }
+
+    pub fn get<T, O, H>(self, pattern: &str, handler: H) -> Router
+    where
+        O: Into<ft_sdk::Result<http::Response<bytes::Bytes>>>,
+        H: ft_sdk::from_request::handler::Handler<T, O> + Copy + 'static,
+    {
+        self.with_route(Route::new(Some("GET"), pattern, handler))
+    }
+
+    pub fn post<T, O, H>(self, pattern: &str, handler: H) -> Router
+    where
+        O: Into<ft_sdk::Result<http::Response<bytes::Bytes>>>,
+        H: ft_sdk::from_request::handler::Handler<T, O> + Copy + 'static,
+    {
+        self.with_route(Route::new(Some("POST"), pattern, handler))
+    }
+
+    /// Read the current request, dispatch it to the matching route, and send the response.
+    ///
+    /// This is what the entrypoint generated by `ft_sdk::router!` calls.
+    pub fn handle(self) {
+        let middleware: Vec<&dyn ft_sdk::Middleware> =
+            self.middleware.iter().map(|m| m.as_ref()).collect();
+        ft_sdk::from_request::handler::serve(&middleware, |req| self.call(req))
+    }
+
+    /// Dispatch `req` to the first route matching its path and method.
+    pub fn call(
+        &self,
+        req: &mut http::Request<serde_json::Value>,
+    ) -> ft_sdk::Result<http::Response<bytes::Bytes>> {
+        let path = match split_path(req.uri().path())
+            .map(ft_sdk::utils::percent_decode)
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(v) => v,
+            None => {
+                return Err(ft_sdk::not_found!("invalid path: {}", req.uri().path()).into());
+            }
+        };
+
+        let mut allowed = vec![];
+        for route in self.routes.iter() {
+            let params = match route.match_path(&path) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            match route.method {
+                Some(ref m) if m != req.method() => {
+                    allowed.push(m.to_string());
+                    continue;
+                }
+                _ => {}
+            }
+
+            req.extensions_mut().insert(params);
+            return (route.handler)(req);
+        }
+
+        if !allowed.is_empty() {
+            return Err(ft_sdk::SpecialError::MethodNotAllowed(allowed).into());
+        }
+
+        Err(ft_sdk::not_found!("no route for {}", req.uri().path()).into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[derive(serde::Deserialize)]
+    struct Item {
+        id: i64,
+        slug: String,
+    }
+
+    fn item(ft_sdk::PathParams(item): ft_sdk::PathParams<Item>) -> ft_sdk::processor::Result {
+        ft_sdk::processor::json(format!("{}:{}", item.id, item.slug))
+    }
+
+    fn list() -> ft_sdk::processor::Result {
+        ft_sdk::processor::json("list")
+    }
+
+    fn router() -> super::Router {
+        super::Router::new()
+            .get("/items/", list)
+            .with_route(super::Route::new(Some("post"), "/items/{id}/{slug}/", item))
+    }
+
+    fn call(method: &str, path: &str) -> ft_sdk::Result<String> {
+        let mut req = http::Request::builder()
+            .method(method)
+            .uri(path)
+            .body(serde_json::Value::Null)
+            .unwrap();
+        let r = router().call(&mut req)?;
+        Ok(String::from_utf8(r.body().to_vec()).unwrap())
+    }
+
+    #[test]
+    fn dispatch() {
+        assert_eq!(call("GET", "/items/").unwrap(), "\"list\"");
+        assert_eq!(call("GET", "/items").unwrap(), "\"list\"");
+        assert_eq!(call("POST", "/items/42/a%20b").unwrap(), "\"42:a b\"");
+    }
+
+    #[test]
+    fn not_found() {
+        assert_eq!(
+            call("GET", "/nope/").unwrap_err().downcast_ref(),
+            Some(&ft_sdk::not_found!("no route for /nope/"))
+        );
+    }
+
+    #[test]
+    fn method_not_allowed() {
+        assert_eq!(
+            call("GET", "/items/1/x/").unwrap_err().downcast_ref(),
+            Some(&ft_sdk::SpecialError::MethodNotAllowed(vec![
+                "POST".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn invalid_param() {
+        assert!(call("POST", "/items/abc/x/").is_err());
+    }
+}
@@ -9,3 +9,29 @@ diesel::table! {
         created_at -> Timestamptz,
     }
 }
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    fastn_tracker (id) {
+        id -> Text,
+        uid -> Nullable<Int8>,
+        first_seen -> Timestamptz,
+        last_seen -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    fastn_event (id) {
+        id -> Int8,
+        tracker_id -> Text,
+        name -> Text,
+        props -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::joinable!(fastn_event -> fastn_tracker (tracker_id));
+diesel::allow_tables_to_appear_in_same_query!(fastn_event, fastn_tracker);
@@ -0,0 +1,101 @@
+//! The browser of the current request, saved in the session data when a session is created.
+
+/// Key in the session data holding the [Client] the session was created from.
+pub const SESSION_CLIENT_KEY: &str = "fastn-client";
+
+/// The browser a session was created from, captured by [ft_sdk::SessionID::create] from the
+/// request. The ip is the first address in the `X-Forwarded-For` header, or `X-Real-IP`, as set
+/// by the proxy in front of the app, so it is only as trustworthy as that proxy.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Client {
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub ip: Option<String>,
+}
+
+impl Client {
+    fn from_request(req: &http::Request<serde_json::Value>) -> Client {
+        let header = |name: http::HeaderName| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+        let forwarded_for =
+            header(http::HeaderName::from_static("x-forwarded-for")).and_then(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .find(|v| !v.is_empty())
+                    .map(str::to_string)
+            });
+
+        Client {
+            user_agent: header(http::header::USER_AGENT),
+            ip: forwarded_for.or_else(|| header(http::HeaderName::from_static("x-real-ip"))),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.user_agent.is_none() && self.ip.is_none()
+    }
+}
+
+thread_local! {
+    static CURRENT: std::cell::RefCell<Client> = std::cell::RefCell::new(Client::default());
+}
+
+/// Remember the client of the current request, called when the request is read.
+pub(crate) fn set_current(req: &http::Request<serde_json::Value>) {
+    CURRENT.with(|c| *c.borrow_mut() = Client::from_request(req));
+}
+
+/// Add the client of the current request to `data`, unless it is not an object or nothing is
+/// known about the client.
+pub(crate) fn add_current(data: &mut serde_json::Value) {
+    let client = CURRENT.with(|c| c.borrow().clone());
+    if client.is_empty() {
+        return;
+    }
+    if let Some(data) = data.as_object_mut() {
+        // a Client always serializes
+        data.insert(
+            SESSION_CLIENT_KEY.to_string(),
+            serde_json::to_value(client).unwrap_or_default(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn from_request() {
+        let req = http::Request::builder()
+            .header("user-agent", "Mozilla/5.0")
+            .header("x-forwarded-for", " 203.0.113.7, 10.0.0.1")
+            .header("x-real-ip", "10.0.0.1")
+            .body(serde_json::Value::Null)
+            .unwrap();
+        assert_eq!(
+            super::Client::from_request(&req),
+            super::Client {
+                user_agent: Some("Mozilla/5.0".to_string()),
+                ip: Some("203.0.113.7".to_string()),
+            }
+        );
+
+        let req = http::Request::builder()
+            .header("x-real-ip", "10.0.0.1")
+            .body(serde_json::Value::Null)
+            .unwrap();
+        assert_eq!(
+            super::Client::from_request(&req),
+            super::Client {
+                user_agent: None,
+                ip: Some("10.0.0.1".to_string()),
+            }
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Session timeouts.
+//!
+//! A session expires the absolute timeout, [ABSOLUTE_TIMEOUT_ENV], after it was created, or the
+//! idle timeout, [IDLE_TIMEOUT_ENV], after it was last used, whichever comes first. Expired
+//! sessions are deleted when they are next resolved from the session cookie, and by [gc] for the
+//! ones that never are.
+//!
+//! Using a session moves its `updated_at` forward, at most once every
+//! [RENEW_INTERVAL_SECONDS], so not every request writes to the database.
+
+/// Environment variable holding the absolute timeout in seconds, [DEFAULT_ABSOLUTE_TIMEOUT]
+/// if not set, `0` disables it.
+pub const ABSOLUTE_TIMEOUT_ENV: &str = "SESSION_ABSOLUTE_TIMEOUT";
+/// Environment variable holding the idle timeout in seconds, [DEFAULT_IDLE_TIMEOUT] if not
+/// set, `0` disables it.
+pub const IDLE_TIMEOUT_ENV: &str = "SESSION_IDLE_TIMEOUT";
+/// 30 days.
+pub const DEFAULT_ABSOLUTE_TIMEOUT: i64 = 30 * 24 * 60 * 60;
+/// 7 days.
+pub const DEFAULT_IDLE_TIMEOUT: i64 = 7 * 24 * 60 * 60;
+/// `updated_at` of a session is not moved forward more often than this.
+pub const RENEW_INTERVAL_SECONDS: i64 = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Timeouts {
+    absolute: Option<chrono::Duration>,
+    idle: Option<chrono::Duration>,
+}
+
+impl Timeouts {
+    fn from_env() -> Timeouts {
+        Timeouts {
+            absolute: timeout(ABSOLUTE_TIMEOUT_ENV, DEFAULT_ABSOLUTE_TIMEOUT),
+            idle: timeout(IDLE_TIMEOUT_ENV, DEFAULT_IDLE_TIMEOUT),
+        }
+    }
+
+    fn is_expired(
+        &self,
+        created_at: chrono::DateTime<chrono::Utc>,
+        updated_at: chrono::DateTime<chrono::Utc>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> bool {
+        // a timeout so long it overflows the date never expires
+        let passed = |since: chrono::DateTime<chrono::Utc>, t| {
+            since.checked_add_signed(t).is_some_and(|end| end <= now)
+        };
+        self.absolute.is_some_and(|t| passed(created_at, t))
+            || self.idle.is_some_and(|t| passed(updated_at, t))
+    }
+}
+
+fn timeout(env: &str, default: i64) -> Option<chrono::Duration> {
+    let seconds = ft_sys::env::var(env.to_string())
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .unwrap_or(default);
+    parse_seconds(seconds)
+}
+
+/// `None`, no timeout, for `0` or less, or more seconds than a [chrono::Duration] holds.
+fn parse_seconds(seconds: i64) -> Option<chrono::Duration> {
+    if seconds <= 0 {
+        return None;
+    }
+    chrono::Duration::try_seconds(seconds)
+}
+
+fn needs_renewal(
+    updated_at: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> bool {
+    updated_at
+        .checked_add_signed(chrono::Duration::seconds(RENEW_INTERVAL_SECONDS))
+        .is_some_and(|t| t <= now)
+}
+
+/// Check the session read from the session cookie. An expired session is deleted and `false`
+/// returned, else its `updated_at` is renewed if due.
+pub(crate) fn check(
+    conn: &mut ft_sdk::Connection,
+    session_id: &str,
+    created_at: chrono::DateTime<chrono::Utc>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+) -> Result<bool, diesel::result::Error> {
+    use diesel::prelude::*;
+    use ft_sdk::schema::fastn_session;
+
+    let now = ft_sdk::env::now();
+    let session = fastn_session::table.filter(fastn_session::id.eq(session_id));
+
+    if Timeouts::from_env().is_expired(created_at, updated_at, now) {
+        diesel::delete(session).execute(conn)?;
+        return Ok(false);
+    }
+
+    if needs_renewal(updated_at, now) {
+        diesel::update(session)
+            .set(fastn_session::updated_at.eq(now))
+            .execute(conn)?;
+    }
+
+    Ok(true)
+}
+
+/// Check the session `session_id` the same way sessions from the session cookie are checked.
+/// `false` if the session does not exist or has expired.
+pub(crate) fn resolve(
+    conn: &mut ft_sdk::Connection,
+    session_id: &str,
+) -> Result<bool, diesel::result::Error> {
+    use diesel::prelude::*;
+    use ft_sdk::schema::fastn_session;
+
+    let row: Option<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)> =
+        fastn_session::table
+            .select((fastn_session::created_at, fastn_session::updated_at))
+            .filter(fastn_session::id.eq(session_id))
+            .first(conn)
+            .optional()?;
+
+    match row {
+        Some((created_at, updated_at)) => check(conn, session_id, created_at, updated_at),
+        None => Ok(false),
+    }
+}
+
+/// Delete the sessions that have expired by `now`. Returns the number of sessions deleted.
+///
+/// Call this periodically, e.g. from an endpoint hit by a cron job, so sessions that are never
+/// used again do not pile up.
+pub fn gc(
+    conn: &mut ft_sdk::Connection,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<usize, diesel::result::Error> {
+    use diesel::prelude::*;
+    use ft_sdk::schema::fastn_session;
+
+    let timeouts = Timeouts::from_env();
+    let mut deleted = 0;
+
+    // no session can be older than a timeout that overflows the date
+    if let Some(before) = timeouts.absolute.and_then(|t| now.checked_sub_signed(t)) {
+        deleted +=
+            diesel::delete(fastn_session::table.filter(fastn_session::created_at.le(before)))
+                .execute(conn)?;
+    }
+    if let Some(before) = timeouts.idle.and_then(|t| now.checked_sub_signed(t)) {
+        deleted +=
+            diesel::delete(fastn_session::table.filter(fastn_session::updated_at.le(before)))
+                .execute(conn)?;
+    }
+
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod test {
+    fn at(seconds: i64) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::from_timestamp(seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn is_expired() {
+        let t = super::Timeouts {
+            absolute: super::parse_seconds(1000),
+            idle: super::parse_seconds(100),
+        };
+        assert!(!t.is_expired(at(0), at(950), at(999)));
+        assert!(t.is_expired(at(0), at(950), at(1000)));
+        assert!(t.is_expired(at(0), at(500), at(600)));
+        assert!(!t.is_expired(at(0), at(500), at(599)));
+
+        let t = super::Timeouts {
+            absolute: super::parse_seconds(0),
+            idle: super::parse_seconds(-1),
+        };
+        assert_eq!(t.absolute, None);
+        assert!(!t.is_expired(at(0), at(0), at(i32::MAX as i64)));
+
+        // too long to be a duration, or to add to a date, is no timeout
+        assert_eq!(super::parse_seconds(i64::MAX), None);
+        let t = super::Timeouts {
+            absolute: super::parse_seconds(i64::MAX / 1001),
+            idle: None,
+        };
+        assert!(t.absolute.is_some());
+        assert!(!t.is_expired(at(0), at(0), at(i32::MAX as i64)));
+    }
+
+    #[test]
+    fn needs_renewal() {
+        assert!(!super::needs_renewal(at(100), at(159)));
+        assert!(super::needs_renewal(at(100), at(160)));
+    }
+}
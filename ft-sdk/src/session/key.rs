@@ -0,0 +1,95 @@
+/// A key of the session data, with the type of its value, so every use of the key agrees on it.
+///
+/// ```rust,ignore
+/// const CART: ft_sdk::session::SessionKey<Vec<String>> = ft_sdk::session::SessionKey::new("cart");
+///
+/// CART.update(&mut conn, &session_id, |items| {
+///     let mut items = items.unwrap_or_default();
+///     items.push(item);
+///     Some(items)
+/// })?;
+/// let items = CART.get(&mut conn, &session_id)?.unwrap_or_default();
+/// ```
+pub struct SessionKey<T> {
+    name: &'static str,
+    _value: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> SessionKey<T> {
+    pub const fn new(name: &'static str) -> SessionKey<T> {
+        SessionKey {
+            name,
+            _value: std::marker::PhantomData,
+        }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T: serde::Serialize + serde::de::DeserializeOwned> SessionKey<T> {
+    /// The value of the key, `None` if it is not set. See [ft_sdk::SessionID::get_key].
+    pub fn get(
+        &self,
+        conn: &mut ft_sdk::Connection,
+        session_id: &ft_sdk::SessionID,
+    ) -> Result<Option<T>, ft_sdk::session::GetKeyError> {
+        match session_id.get_key(conn, self.name) {
+            Ok(v) => Ok(Some(v)),
+            Err(ft_sdk::session::GetKeyError::KeyNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// See [ft_sdk::SessionID::set_key].
+    pub fn set(
+        &self,
+        conn: &mut ft_sdk::Connection,
+        session_id: &ft_sdk::SessionID,
+        value: &T,
+    ) -> Result<(), ft_sdk::session::SetKeyError> {
+        session_id.set_key(conn, self.name, value)?;
+        Ok(())
+    }
+
+    /// See [ft_sdk::SessionID::remove_key].
+    pub fn remove(
+        &self,
+        conn: &mut ft_sdk::Connection,
+        session_id: &ft_sdk::SessionID,
+    ) -> Result<(), ft_sdk::session::SetKeyError> {
+        session_id.remove_key(conn, self.name)?;
+        Ok(())
+    }
+
+    /// See [ft_sdk::SessionID::update_key].
+    pub fn update<F: FnOnce(Option<T>) -> Option<T>>(
+        &self,
+        conn: &mut ft_sdk::Connection,
+        session_id: &ft_sdk::SessionID,
+        f: F,
+    ) -> Result<Option<T>, ft_sdk::session::SetKeyError> {
+        session_id.update_key(conn, self.name, f)
+    }
+}
+
+impl<T> AsRef<str> for SessionKey<T> {
+    fn as_ref(&self) -> &str {
+        self.name
+    }
+}
+
+impl<T> Clone for SessionKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SessionKey<T> {}
+
+impl<T> std::fmt::Debug for SessionKey<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SessionKey").field(&self.name).finish()
+    }
+}
@@ -1,6 +1,14 @@
+mod client;
+pub mod expiry;
+mod key;
 mod session_data;
+pub(crate) mod sql;
 
+pub(crate) use client::set_current as set_current_client;
+pub use client::{Client, SESSION_CLIENT_KEY};
+pub use expiry::gc;
 pub use ft_sys_shared::TRACKER_KEY;
+pub use key::SessionKey;
 pub use session_data::SessionData;
 
 #[derive(Clone, Debug)]
@@ -9,6 +17,9 @@ pub struct SessionID(pub String);
 impl SessionID {
     /// Create a new session entry with the given user ID.
     /// If the user ID is None, the session will be created without a user ID.
+    ///
+    /// The [Client] of the current request is stored in the data under [SESSION_CLIENT_KEY], see
+    /// [list_for_user].
     pub fn create(
         conn: &mut ft_sdk::Connection,
         user_id: Option<ft_sdk::auth::UserId>,
@@ -19,10 +30,9 @@ impl SessionID {
 
         let session_id = ft_sdk::utils::uuid_v8();
 
-        let data = match data {
-            Some(d) => serde_json::to_string(&d)?,
-            None => "{}".to_string(),
-        };
+        let mut data = data.unwrap_or_else(|| serde_json::json!({}));
+        client::add_current(&mut data);
+        let data = serde_json::to_string(&data)?;
 
         let user_id = user_id.map(|u| u.0);
 
@@ -57,6 +67,7 @@ impl SessionID {
                 // None means that the field will not be updated
                 .set(fastn_session::uid.eq(Some(user_id.0)))
                 .execute(conn)?;
+        ft_sdk::auth::current::invalidate();
 
         assert_eq!(
             affected, 1,
@@ -68,6 +79,111 @@ using `SessionID::new`"#
         Ok(self.clone())
     }
 
+    /// Move the session to a new id, with the same user and data, and delete this one. Used when
+    /// the privileges of the session change, e.g. on login, so an id planted in the browser
+    /// before (session fixation) is of no use after.
+    ///
+    /// If this is the session of the current request, the rest of the request uses the new id,
+    /// and the session cookie is set to it on the response. Otherwise the caller has to send the
+    /// cookie, see [SessionID::cookie].
+    pub fn rotate(&self, conn: &mut ft_sdk::Connection) -> Result<SessionID, ft_sdk::Error> {
+        self.rotate_to(conn, None)
+    }
+
+    /// [SessionID::rotate], logging `user_id` in to the new session. The auth state of another
+    /// user, or of no user, is not carried over, see [rotated_data].
+    #[cfg(feature = "auth-provider")]
+    pub(crate) fn rotate_for_user(
+        &self,
+        conn: &mut ft_sdk::Connection,
+        user_id: &ft_sdk::UserId,
+    ) -> Result<SessionID, ft_sdk::Error> {
+        self.rotate_to(conn, Some(user_id))
+    }
+
+    fn rotate_to(
+        &self,
+        conn: &mut ft_sdk::Connection,
+        user_id: Option<&ft_sdk::UserId>,
+    ) -> Result<SessionID, ft_sdk::Error> {
+        use diesel::prelude::*;
+        use ft_sdk::schema::fastn_session;
+
+        let new = conn.transaction::<_, ft_sdk::Error, _>(|conn| {
+            let session = fastn_session::table.filter(fastn_session::id.eq(self.0.as_str()));
+            let (uid, data): (Option<i64>, String) = session
+                .select((fastn_session::uid, fastn_session::data))
+                .first(conn)?;
+            let new_uid = user_id.map(|u| u.0).or(uid);
+            let data = rotated_data(serde_json::from_str(&data)?, uid, new_uid);
+            let new = SessionID::create(conn, new_uid.map(ft_sdk::UserId), Some(data))?;
+            diesel::delete(session).execute(conn)?;
+            Ok(new)
+        })?;
+
+        if ft_sdk::auth::session_id().is_some_and(|s| s.0 == self.0) {
+            ft_sdk::auth::current::set_session_id(Some(new.0.clone()));
+            ft_sdk::chr::add_pending_cookie(new.cookie());
+        }
+
+        Ok(new)
+    }
+
+    /// The session cookie holding this id, to send after creating or rotating a session.
+    ///
+    /// `SameSite=Lax`, as with `Strict` the cookie is not sent when following a link from another
+    /// site, and the user would look logged out.
+    pub fn cookie(&self) -> ft_sdk::cookie::SetCookie {
+        ft_sdk::cookie::SetCookie::new(ft_sdk::auth::SESSION_KEY, self.0.as_str())
+            .with_path("/")
+            .with_same_site(ft_sdk::cookie::SameSite::Lax)
+    }
+
+    /// Log the user out of this session. With `delete` the session is removed, else only the user
+    /// and the auth state kept in the session data are cleared, and the rest of the data stays.
+    ///
+    /// The returned cookie removes the session cookie from the browser:
+    ///
+    /// ```rust,ignore
+    /// let cookie = session_id.logout(&mut conn, true)?;
+    /// ft_sdk::data::browser_redirect_with_cookie("/", cookie)
+    /// ```
+    ///
+    /// If this is the session of the current request, the rest of the request has no session,
+    /// and the cookie is removed on the response without the caller sending it.
+    pub fn logout(
+        &self,
+        conn: &mut ft_sdk::Connection,
+        delete: bool,
+    ) -> Result<ft_sdk::cookie::SetCookie, diesel::result::Error> {
+        use diesel::prelude::*;
+        use ft_sdk::schema::fastn_session;
+
+        if delete {
+            revoke(conn, self)?;
+        } else {
+            conn.transaction::<_, diesel::result::Error, _>(|conn| {
+                diesel::update(fastn_session::table.filter(fastn_session::id.eq(self.0.as_str())))
+                    .set(fastn_session::uid.eq(None::<i64>))
+                    .execute(conn)?;
+                let mut data = self.data(conn)?;
+                for key in ft_sdk::auth::SESSION_AUTH_KEYS {
+                    data.remove(key);
+                }
+                data.persist(conn)
+            })?;
+            ft_sdk::auth::current::invalidate();
+        }
+
+        let removal = ft_sdk::cookie::SetCookie::removal(ft_sdk::auth::SESSION_KEY).with_path("/");
+        if ft_sdk::auth::session_id().is_some_and(|s| s.0 == self.0) {
+            ft_sdk::auth::current::set_session_id(None);
+            ft_sdk::chr::add_pending_cookie(removal.clone());
+        }
+
+        Ok(removal)
+    }
+
     /// Get the session data object.
     /// Useful for fetching the entire session data in a single db call. Use
     /// [get_key](SessionID::get_key) instead if you only need a single key
@@ -89,37 +205,223 @@ using `SessionID::new`"#
         Ok(SessionData::new(self.0.as_str(), data))
     }
 
+    /// Get the value of a single key of the session data. A key set to `null` is not found.
+    pub fn get_key<S: AsRef<str>, V: serde::de::DeserializeOwned>(
+        &self,
+        conn: &mut ft_sdk::Connection,
+        k: S,
+    ) -> Result<V, GetKeyError> {
+        let k = k.as_ref();
+        let v: Option<serde_json::Value> = self
+            .data(conn)
+            .map_err(GetKeyError::DatabaseError)?
+            .get_key(k);
+        match v {
+            None | Some(serde_json::Value::Null) => Err(GetKeyError::KeyNotFound(k.to_string())),
+            Some(v) => serde_json::from_value(v).map_err(GetKeyError::SerdeError),
+        }
+    }
+
     /// Directly store a key-value in the session store. This will overwrite the existing value for
     /// the given key if it exists.
     /// This is useful for storing a single key-value pair without fetching the entire session data
+    ///
+    /// Only this key is written, so concurrent changes to other keys are kept. Keys can not be
+    /// empty or contain `"`. Keys starting with `fastn-` are used by ft-sdk itself, e.g.
+    /// [SESSION_CLIENT_KEY].
     pub fn set_key<S: AsRef<str>, V: serde::Serialize>(
         &self,
         conn: &mut ft_sdk::Connection,
         k: S,
         v: V,
     ) -> Result<SessionID, SetKeyError> {
+        let k = valid_key(k.as_ref())?;
+        let value = serde_json::to_string(&v).map_err(SetKeyError::SerdeError)?;
+        sql::set(conn, self.0.as_str(), k, value)?;
+        Ok(self.clone())
+    }
+
+    /// Remove a key from the session data, see [SessionID::set_key].
+    pub fn remove_key<S: AsRef<str>>(
+        &self,
+        conn: &mut ft_sdk::Connection,
+        k: S,
+    ) -> Result<SessionID, SetKeyError> {
+        let k = valid_key(k.as_ref())?;
+        sql::remove(conn, self.0.as_str(), k)?;
+        Ok(self.clone())
+    }
+
+    /// Change the value of a key based on its current value, `None` if it is not set. Returning
+    /// `None` removes the key. Returns the new value.
+    ///
+    /// The session is locked while `f` runs, so concurrent updates of the key wait for each other,
+    /// and none is lost.
+    pub fn update_key<S, V, F>(
+        &self,
+        conn: &mut ft_sdk::Connection,
+        k: S,
+        f: F,
+    ) -> Result<Option<V>, SetKeyError>
+    where
+        S: AsRef<str>,
+        V: serde::Serialize + serde::de::DeserializeOwned,
+        F: FnOnce(Option<V>) -> Option<V>,
+    {
         use diesel::prelude::*;
-        use diesel::sql_types::Text;
-        use ft_sdk::schema::fastn_session;
 
-        let value = serde_json::to_string(&v).map_err(SetKeyError::SerdeError)?;
+        let k = valid_key(k.as_ref())?;
+        conn.transaction(|conn| {
+            sql::lock(conn, self.0.as_str())?;
+            let current: Option<serde_json::Value> = self.data(conn)?.get_key(k);
+            let current = current
+                .filter(|v| !v.is_null())
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(SetKeyError::SerdeError)?;
 
-        // json_set(data, '.<key>', json('<value>'))
-        let sql_set = diesel::dsl::sql::<Text>("json_set(data, ")
-            .bind::<Text, _>(format!("$.{}", k.as_ref()))
-            .sql(", json(")
-            .bind::<Text, _>(value)
-            .sql("))");
+            let new = f(current);
+            match &new {
+                Some(v) => {
+                    let value = serde_json::to_string(v).map_err(SetKeyError::SerdeError)?;
+                    sql::set(conn, self.0.as_str(), k, value)?
+                }
+                None => sql::remove(conn, self.0.as_str(), k)?,
+            };
+            Ok(new)
+        })
+    }
+
+    /// Remove all keys from the session data, except [SESSION_CLIENT_KEY], which is about the
+    /// session, not its data.
+    pub fn clear(&self, conn: &mut ft_sdk::Connection) -> Result<SessionID, SetKeyError> {
+        use diesel::prelude::*;
+        use ft_sdk::schema::fastn_session;
+
+        conn.transaction::<_, SetKeyError, _>(|conn| {
+            sql::lock(conn, self.0.as_str())?;
+            let client: Option<serde_json::Value> = self.data(conn)?.get_key(SESSION_CLIENT_KEY);
+            let mut data = serde_json::Map::new();
+            if let Some(client) = client {
+                data.insert(SESSION_CLIENT_KEY.to_string(), client);
+            }
 
-        diesel::update(fastn_session::table.filter(fastn_session::id.eq(self.0.as_str())))
-            .set(fastn_session::data.eq(sql_set))
-            .execute(conn)
-            .map_err(SetKeyError::DatabaseError)?;
+            diesel::update(fastn_session::table.filter(fastn_session::id.eq(self.0.as_str())))
+                .set(fastn_session::data.eq(serde_json::Value::Object(data).to_string()))
+                .execute(conn)?;
+            Ok(())
+        })?;
+        ft_sdk::auth::current::invalidate();
 
         Ok(self.clone())
     }
 }
 
+fn valid_key(k: &str) -> Result<&str, SetKeyError> {
+    if sql::is_valid_key(k) {
+        Ok(k)
+    } else {
+        Err(SetKeyError::InvalidKey(k.to_string()))
+    }
+}
+
+/// A session of a user, as listed by [list_for_user].
+#[derive(Clone, Debug)]
+pub struct SessionInfo {
+    pub id: SessionID,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    /// The browser the session was created from, empty for sessions created before it was
+    /// recorded.
+    pub client: Client,
+    /// This is the session of the current request.
+    pub current: bool,
+}
+
+/// All sessions the user is logged in to, most recently used first. Use [revoke] to log the user
+/// out of one of them.
+pub fn list_for_user(
+    conn: &mut ft_sdk::Connection,
+    user_id: &ft_sdk::UserId,
+) -> Result<Vec<SessionInfo>, diesel::result::Error> {
+    use diesel::prelude::*;
+    use ft_sdk::schema::fastn_session;
+
+    let current = ft_sdk::auth::session_id().map(|s| s.0);
+    let rows: Vec<(
+        String,
+        String,
+        chrono::DateTime<chrono::Utc>,
+        chrono::DateTime<chrono::Utc>,
+    )> = fastn_session::table
+        .select((
+            fastn_session::id,
+            fastn_session::data,
+            fastn_session::created_at,
+            fastn_session::updated_at,
+        ))
+        .filter(fastn_session::uid.eq(user_id.0))
+        .order(fastn_session::updated_at.desc())
+        .load(conn)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, data, created_at, updated_at)| SessionInfo {
+            current: current.as_deref() == Some(id.as_str()),
+            id: SessionID(id),
+            created_at,
+            updated_at,
+            client: session_client(&data),
+        })
+        .collect())
+}
+
+fn session_client(data: &str) -> Client {
+    serde_json::from_str::<serde_json::Value>(data)
+        .ok()
+        .and_then(|mut v| v.get_mut(SESSION_CLIENT_KEY).map(serde_json::Value::take))
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+/// Delete the session, logging out whoever uses it.
+///
+/// The session id is not checked against the current user, when revoking a session picked by the
+/// user, make sure it is one of theirs, e.g. from [list_for_user].
+pub fn revoke(
+    conn: &mut ft_sdk::Connection,
+    session_id: &SessionID,
+) -> Result<(), diesel::result::Error> {
+    use diesel::prelude::*;
+    use ft_sdk::schema::fastn_session;
+
+    diesel::delete(fastn_session::table.filter(fastn_session::id.eq(session_id.0.as_str())))
+        .execute(conn)?;
+    ft_sdk::auth::current::invalidate();
+
+    Ok(())
+}
+
+/// Delete all sessions of the user, except the session of the current request. Returns the
+/// number of sessions deleted.
+pub fn revoke_all_except_current(
+    conn: &mut ft_sdk::Connection,
+    user_id: &ft_sdk::UserId,
+) -> Result<usize, diesel::result::Error> {
+    use diesel::prelude::*;
+    use ft_sdk::schema::fastn_session;
+
+    let sessions = fastn_session::table.filter(fastn_session::uid.eq(user_id.0));
+    let deleted = match ft_sdk::auth::session_id() {
+        Some(current) => {
+            diesel::delete(sessions.filter(fastn_session::id.ne(current.0))).execute(conn)?
+        }
+        None => diesel::delete(sessions).execute(conn)?,
+    };
+
+    Ok(deleted)
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum GetKeyError {
     #[error("key `{0}` not found in session data")]
@@ -133,7 +435,73 @@ pub enum GetKeyError {
 #[derive(thiserror::Error, Debug)]
 pub enum SetKeyError {
     #[error("db error: {0:?}")]
-    DatabaseError(diesel::result::Error),
+    DatabaseError(#[from] diesel::result::Error),
     #[error("failed to serialize value: {0:?}")]
     SerdeError(serde_json::Error),
+    /// Keys can not be empty or contain `"`.
+    #[error("invalid session data key `{0}`")]
+    InvalidKey(String),
+}
+
+/// The data of a session being rotated from user `old` to `new`. The
+/// [ft_sdk::auth::SESSION_AUTH_KEYS] belong to the user, so they are dropped when the user
+/// changes, e.g. scopes granted to whoever used the session before logging in.
+fn rotated_data(
+    mut data: serde_json::Map<String, serde_json::Value>,
+    old: Option<i64>,
+    new: Option<i64>,
+) -> serde_json::Value {
+    if old != new {
+        for key in ft_sdk::auth::SESSION_AUTH_KEYS {
+            data.remove(key);
+        }
+    }
+    serde_json::Value::Object(data)
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn session_client() {
+        assert_eq!(
+            super::session_client(r#"{"fastn-client": {"user_agent": "curl/8.0", "ip": null}}"#),
+            super::Client {
+                user_agent: Some("curl/8.0".to_string()),
+                ip: None,
+            }
+        );
+        assert_eq!(super::session_client("{}"), super::Client::default());
+        assert_eq!(
+            super::session_client(r#"{"fastn-client": 1}"#),
+            super::Client::default()
+        );
+    }
+
+    #[test]
+    fn rotated_data() {
+        let data = || {
+            serde_json::json!({
+                "fastn-auth-scopes": {"github": ["repo"]},
+                "fastn-auth-providers": {"github": ["ann"]},
+                "fastn-2fa-pending": 2,
+                "cart": [1],
+            })
+            .as_object()
+            .unwrap()
+            .clone()
+        };
+
+        assert_eq!(
+            super::rotated_data(data(), Some(1), Some(1)),
+            serde_json::Value::Object(data())
+        );
+        assert_eq!(
+            super::rotated_data(data(), None, Some(1)),
+            serde_json::json!({"cart": [1]})
+        );
+        assert_eq!(
+            super::rotated_data(data(), Some(2), Some(1)),
+            serde_json::json!({"cart": [1]})
+        );
+    }
 }
@@ -2,6 +2,8 @@
 pub struct SessionData {
     session_id: String,
     data: std::collections::HashMap<String, serde_json::Value>,
+    /// Keys set or removed since the data was read, the only ones [SessionData::persist] writes.
+    changed: std::collections::BTreeSet<String>,
 }
 
 impl SessionData {
@@ -12,6 +14,7 @@ impl SessionData {
         SessionData {
             session_id: id.to_string(),
             data,
+            changed: Default::default(),
         }
     }
 
@@ -32,21 +35,40 @@ impl SessionData {
     ) -> Result<(), serde_json::Error> {
         let value = serde_json::to_value(v)?;
         self.data.insert(k.as_ref().to_string(), value);
+        self.changed.insert(k.as_ref().to_string());
 
         Ok(())
     }
 
-    /// Save the session data to the database
+    /// Temporarly remove a key from the session data, returning its value.
+    /// Use [SessionData::persist] to save the data back to the database
+    pub fn remove<S: AsRef<str>>(&mut self, k: S) -> Option<serde_json::Value> {
+        self.changed.insert(k.as_ref().to_string());
+        self.data.remove(k.as_ref())
+    }
+
+    /// Save the keys changed by [SessionData::set] and [SessionData::remove] to the database.
+    /// Other keys are not written, so changes made to them since the data was read are kept.
     pub fn persist(&self, conn: &mut ft_sdk::Connection) -> Result<(), diesel::result::Error> {
         use diesel::prelude::*;
-        use ft_sdk::schema::fastn_session;
 
-        let data = serde_json::to_string(&self.data).unwrap();
-
-        diesel::update(fastn_session::table.filter(fastn_session::id.eq(self.session_id.as_str())))
-            .set(fastn_session::data.eq(data))
-            .execute(conn)?;
+        if self.changed.is_empty() {
+            return Ok(());
+        }
 
-        Ok(())
+        conn.transaction(|conn| {
+            for k in &self.changed {
+                if !ft_sdk::session::sql::is_valid_key(k) {
+                    return Err(diesel::result::Error::QueryBuilderError(
+                        format!("invalid session data key `{k}`").into(),
+                    ));
+                }
+                match self.data.get(k) {
+                    Some(v) => ft_sdk::session::sql::set(conn, &self.session_id, k, v.to_string())?,
+                    None => ft_sdk::session::sql::remove(conn, &self.session_id, k)?,
+                };
+            }
+            Ok(())
+        })
     }
 }
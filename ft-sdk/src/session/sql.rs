@@ -0,0 +1,100 @@
+//! Updates of a single key of the session data, done in the database, so concurrent updates of
+//! other keys are not lost. The JSON functions differ between SQLite and Postgres, the ones of
+//! the backend of [ft_sdk::Connection] are used.
+
+/// Keys are used in JSON paths on SQLite, where a quoted path label can not contain `"`. Rejected
+/// on Postgres too, so data works on both.
+pub(crate) fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && !key.contains('"')
+}
+
+/// Set `key` of the session data to `value`, which is JSON. Returns the number of rows updated.
+pub(crate) fn set(
+    conn: &mut ft_sdk::Connection,
+    session_id: &str,
+    key: &str,
+    value: String,
+) -> Result<usize, diesel::result::Error> {
+    use diesel::prelude::*;
+    use diesel::sql_types::Text;
+
+    #[cfg(feature = "sqlite-default")]
+    let sql = "UPDATE fastn_session SET data = json_set(data, $1, json($2)) WHERE id = $3";
+    #[cfg(feature = "postgres-default")]
+    let sql = "UPDATE fastn_session SET data = jsonb_set(data::jsonb, ARRAY[$1], $2::jsonb)::text \
+               WHERE id = $3";
+
+    let r = diesel::sql_query(sql)
+        .bind::<Text, _>(key_param(key))
+        .bind::<Text, _>(value)
+        .bind::<Text, _>(session_id)
+        .execute(conn)?;
+    ft_sdk::auth::current::invalidate();
+    Ok(r)
+}
+
+/// Remove `key` from the session data. Returns the number of rows updated.
+pub(crate) fn remove(
+    conn: &mut ft_sdk::Connection,
+    session_id: &str,
+    key: &str,
+) -> Result<usize, diesel::result::Error> {
+    use diesel::prelude::*;
+    use diesel::sql_types::Text;
+
+    #[cfg(feature = "sqlite-default")]
+    let sql = "UPDATE fastn_session SET data = json_remove(data, $1) WHERE id = $2";
+    #[cfg(feature = "postgres-default")]
+    let sql = "UPDATE fastn_session SET data = (data::jsonb - $1)::text WHERE id = $2";
+
+    let r = diesel::sql_query(sql)
+        .bind::<Text, _>(key_param(key))
+        .bind::<Text, _>(session_id)
+        .execute(conn)?;
+    ft_sdk::auth::current::invalidate();
+    Ok(r)
+}
+
+/// Lock the session row until the end of the transaction, so updates that read the data first do
+/// not run at the same time.
+pub(crate) fn lock(
+    conn: &mut ft_sdk::Connection,
+    session_id: &str,
+) -> Result<usize, diesel::result::Error> {
+    use diesel::prelude::*;
+    use ft_sdk::schema::fastn_session;
+
+    // a write takes the row lock on Postgres, and the database write lock on SQLite
+    diesel::update(fastn_session::table.filter(fastn_session::id.eq(session_id)))
+        .set(fastn_session::data.eq(fastn_session::data))
+        .execute(conn)
+}
+
+/// A JSON path with the key as a quoted label, so `.` and `[` in the key are not path syntax.
+#[cfg(feature = "sqlite-default")]
+fn key_param(key: &str) -> String {
+    format!("$.\"{key}\"")
+}
+
+/// The key is the only element of the `text[]` path of `jsonb_set`, and the operand of `-`.
+#[cfg(feature = "postgres-default")]
+fn key_param(key: &str) -> String {
+    key.to_string()
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn is_valid_key() {
+        assert!(super::is_valid_key("auth-providers"));
+        assert!(super::is_valid_key("a.b[0]"));
+        assert!(!super::is_valid_key(""));
+        assert!(!super::is_valid_key("a\"b"));
+    }
+
+    #[cfg(feature = "sqlite-default")]
+    #[test]
+    fn key_param() {
+        assert_eq!(super::key_param("a.b"), "$.\"a.b\"");
+    }
+}
@@ -40,3 +40,38 @@ pub fn uuid_v8() -> String {
     ft_sdk::Rng::fill_bytes(&mut ft_sdk::Rng {}, &mut rand_buf);
     uuid::Uuid::new_v8(rand_buf).to_string()
 }
+
+/// Decode `%XX` escapes in a URL component. Returns `None` if an escape is malformed or the
+/// decoded bytes are not valid UTF-8.
+pub(crate) fn percent_decode(s: &str) -> Option<String> {
+    let b = s.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+
+    while i < b.len() {
+        if b[i] != b'%' {
+            out.push(b[i]);
+            i += 1;
+            continue;
+        }
+
+        let hex = b.get(i + 1..i + 3)?;
+        if !hex.iter().all(u8::is_ascii_hexdigit) {
+            return None;
+        }
+        out.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+        i += 3;
+    }
+
+    String::from_utf8(out).ok()
+}
+
+/// Compare two secrets in constant time, so they can not be guessed byte by byte from how long
+/// the comparison takes.
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}